@@ -5,14 +5,227 @@
 
 use std::path::PathBuf;
 
+use clap::{ArgMatches, CommandFactory as _, error::ErrorKind, parser::ValueSource};
+
+use crate::prefs::SectionRange;
+
 #[derive(clap::Parser, Debug, Clone)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct Cli {
     /// The Firefox profile directory to operate on; defaults to first installation's default profile in profiles.ini
     #[clap(short, long, global = true, env = "ARKENCRAB_PROFILE")]
     pub profile: Option<PathBuf>,
 
+    /// Operate on the profile with this index in profiles.ini (e.g. 0 for the Profile0 section)
+    #[clap(long, global = true, env = "ARKENCRAB_PROFILE_INDEX")]
+    pub profile_index: Option<usize>,
+
+    /// Operate on the profile that a running Firefox currently has open
+    #[clap(long, global = true, env = "ARKENCRAB_RUNNING")]
+    pub running: bool,
+
+    /// Print additional details about what is being done
+    #[clap(short, long, global = true, env = "ARKENCRAB_VERBOSE")]
+    pub verbose: bool,
+
+    /// Delete the oldest backups after each backup until each backup directory is at most this size (e.g. 500K, 10M, 1G)
+    #[clap(long, global = true, value_parser = parse_size, env = "ARKENCRAB_MAX_BACKUPS_SIZE")]
+    pub max_backups_size: Option<u64>,
+
+    /// The user agent to send with HTTP requests instead of arkencrab's own
+    #[clap(long, global = true, env = "ARKENCRAB_USER_AGENT")]
+    pub user_agent: Option<String>,
+
+    /// Write through profile files that are symlinks, modifying the files they point to
+    #[clap(long, global = true, env = "ARKENCRAB_FOLLOW_SYMLINK")]
+    pub follow_symlink: bool,
+
+    /// Replace profile files that are symlinks with regular files before writing to them
+    #[clap(
+        long,
+        global = true,
+        conflicts_with = "follow_symlink",
+        env = "ARKENCRAB_REPLACE_SYMLINK"
+    )]
+    pub replace_symlink: bool,
+
+    /// Read files back after writing them, restoring the backup if they do not match
+    #[clap(long, global = true, env = "ARKENCRAB_VERIFY_AFTER_WRITE")]
+    pub verify_after_write: bool,
+
+    /// Keep a `<name>.backup.latest` link pointing at the newest backup in each backup directory
+    #[clap(long, global = true, env = "ARKENCRAB_LATEST_BACKUP_LINK")]
+    pub latest_backup_link: bool,
+
+    /// Back up and rewrite user.js even if updating or applying overrides would leave it unchanged
+    #[clap(long, global = true, env = "ARKENCRAB_KEEP_BACKUP_ON_UNCHANGED")]
+    pub keep_backup_on_unchanged: bool,
+
+    /// A comment to place after the overrides marker in user.js; {version}, {arkencrab} and {date} are replaced with the arkenfox version, arkencrab version and current date
+    #[clap(
+        long,
+        global = true,
+        value_name = "TEXT",
+        env = "ARKENCRAB_OVERRIDES_HEADER"
+    )]
+    pub overrides_header: Option<String>,
+
+    /// Write a JSON summary of the run to this file
+    #[clap(long, global = true, env = "ARKENCRAB_REPORT")]
+    pub report: Option<PathBuf>,
+
+    /// Append to the report file as JSON lines instead of overwriting it
+    #[clap(
+        long,
+        global = true,
+        requires = "report",
+        env = "ARKENCRAB_REPORT_APPEND"
+    )]
+    pub report_append: bool,
+
+    /// A directory of overrides shared by every profile: its user-overrides.js is applied before the overrides of the profile, and its user-overrides.<NAME>.js after them for the profile named NAME, always winning
+    #[clap(
+        long,
+        global = true,
+        value_name = "DIR",
+        env = "ARKENCRAB_OVERRIDES_DIR"
+    )]
+    pub overrides_dir: Option<PathBuf>,
+
+    /// How to resolve prefs set by more than one of the shared user-overrides.js, the user-overrides.js of the profile and the files in its user-overrides.d
+    #[clap(
+        long,
+        global = true,
+        value_enum,
+        default_value_t,
+        env = "ARKENCRAB_MERGE_STRATEGY"
+    )]
+    pub merge_strategy: MergeStrategy,
+
+    /// How to print the paths of the profile and backups
+    #[clap(
+        long,
+        global = true,
+        value_enum,
+        default_value_t,
+        env = "ARKENCRAB_RELATIVE_TO"
+    )]
+    pub relative_to: PathDisplay,
+
+    /// Print a summary of the run to stdout as stable `key=value` lines for scripting
+    #[clap(long, global = true, env = "ARKENCRAB_PORCELAIN")]
+    pub porcelain: bool,
+
+    /// Print only the path of the profile that would be used and exit, without running a command
+    #[clap(long)]
+    pub print_profile: bool,
+
+    /// Print a starter user-overrides.js and exit, without running a command
+    #[clap(long, conflicts_with = "print_profile")]
+    pub template_out: bool,
+
     #[clap(subcommand)]
-    pub command: Command,
+    pub command: Option<Command>,
+}
+
+impl Cli {
+    /// Settle which of `--profile`, `--profile-index` and `--running` selects the profile.
+    /// One passed on the command line overrides the others when they are only set in the
+    /// environment; passing more than one on the command line is an error.
+    pub fn select_profile(&mut self, matches: &ArgMatches) -> Result<(), clap::Error> {
+        let explicit = ["profile", "profile_index", "running"]
+            .into_iter()
+            .filter(|id| matches.value_source(id) == Some(ValueSource::CommandLine))
+            .collect::<Vec<_>>();
+
+        match explicit[..] {
+            [] => {}
+            [id] => {
+                if id != "profile" {
+                    self.profile = None;
+                }
+                if id != "profile_index" {
+                    self.profile_index = None;
+                }
+                if id != "running" {
+                    self.running = false;
+                }
+            }
+            _ => {
+                return Err(Self::command().error(
+                    ErrorKind::ArgumentConflict,
+                    "only one of `--profile`, `--profile-index` and `--running` can be used",
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Parse a size in bytes with an optional binary `K`, `M`, or `G` suffix (e.g. `10M`).
+fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let (number, multiplier) = match s.char_indices().last() {
+        Some((i, 'k' | 'K')) => (&s[..i], 1 << 10),
+        Some((i, 'm' | 'M')) => (&s[..i], 1 << 20),
+        Some((i, 'g' | 'G')) => (&s[..i], 1 << 30),
+        _ => (s, 1),
+    };
+
+    number
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .ok_or_else(|| {
+            format!(
+                "invalid size `{s}`; expected a number of bytes, optionally followed by K, M, or G"
+            )
+        })
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MergeStrategy {
+    /// Keep every value; the last one set takes effect, as in Firefox
+    #[default]
+    LastWins,
+    /// Drop values for prefs that were already set by an earlier file
+    FirstWins,
+    /// Fail, listing the prefs that are set by more than one file
+    Error,
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PathDisplay {
+    /// Print paths inside the profile relative to the profile
+    #[default]
+    Profile,
+    /// Print paths relative to the current directory
+    Cwd,
+    /// Print absolute paths
+    Absolute,
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FileKind {
+    /// user.js
+    UserJs,
+    /// prefs.js
+    PrefsJs,
+    /// Both user.js and prefs.js
+    #[default]
+    All,
+}
+
+impl FileKind {
+    pub fn file_names(self) -> &'static [&'static str] {
+        match self {
+            Self::UserJs => &["user.js"],
+            Self::PrefsJs => &["prefs.js"],
+            Self::All => &["user.js", "prefs.js"],
+        }
+    }
 }
 
 #[derive(clap::Subcommand, Debug, Clone)]
@@ -23,17 +236,57 @@ pub enum Command {
         #[clap(short, long, env = "ARKENCRAB_DIFF")]
         diff: bool,
 
+        /// Save a plain unified diff of the changes to this file
+        #[clap(long)]
+        diff_output: Option<PathBuf>,
+
         /// Don't add overrides from user-overrides.js
         #[clap(short, long, env = "ARKENCRAB_UPDATE_NO_OVERRIDES")]
         no_overrides: bool,
 
-        /// Reference in the arkenfox repository to use
+        /// Read the overrides from standard input instead of user-overrides.js
+        #[clap(long, conflicts_with = "no_overrides")]
+        stdin_overrides: bool,
+
+        /// Branch or tag in the arkenfox repository to use (see --list-versions)
         #[clap(short, long, default_value_t = String::from("master"))]
         r#ref: String,
 
+        /// List the available arkenfox versions instead of updating
+        #[clap(long)]
+        list_versions: bool,
+
+        /// Print the list of versions as JSON
+        #[clap(long, requires = "list_versions")]
+        json: bool,
+
+        /// A command to run after user.js has been changed, with `ARKENCRAB_OLD_VERSION`, `ARKENCRAB_NEW_VERSION`, `ARKENCRAB_PROFILE` and `ARKENCRAB_BACKUP` set
+        #[clap(long, env = "ARKENCRAB_HOOK")]
+        hook: Option<String>,
+
         /// Enable preferences for Firefox ESR
         #[clap(long, env = "ARKENCRAB_ESR")]
         esr: bool,
+
+        /// Remove comments and blank lines from the generated user.js
+        #[clap(long, env = "ARKENCRAB_STRIP_COMMENTS")]
+        strip_comments: bool,
+
+        /// Write user.js even if the fetched file does not look like a valid arkenfox user.js or is older than the installed one
+        #[clap(short, long)]
+        force: bool,
+
+        /// Exit with an error instead of writing if user.js would change (for detecting drift in CI)
+        #[clap(long, env = "ARKENCRAB_FAIL_ON_CHANGE")]
+        fail_on_change: bool,
+
+        /// Describe what the update will do and ask for confirmation before doing it
+        #[clap(long)]
+        explain: bool,
+
+        /// Don't ask for confirmation
+        #[clap(short, long)]
+        yes: bool,
     },
 
     /// Clean redundant preferences in prefs.js
@@ -41,6 +294,22 @@ pub enum Command {
         /// Show a diff of the changes (will be large)
         #[clap(short, long, env = "ARKENCRAB_DIFF")]
         diff: bool,
+
+        /// Save a plain unified diff of the changes to this file
+        #[clap(long, conflicts_with = "stats_only")]
+        diff_output: Option<PathBuf>,
+
+        /// Only report how many redundant prefs there are without modifying prefs.js
+        #[clap(short, long, conflicts_with = "diff")]
+        stats_only: bool,
+
+        /// Only consider prefs in this arkenfox section or range of sections (e.g. 0800 or 0800-0900); requires an arkenfox user.js
+        #[clap(long)]
+        section: Option<SectionRange>,
+
+        /// Refuse to run unless user.js is managed by arkencrab and sets at least one pref
+        #[clap(long, env = "ARKENCRAB_REQUIRE_MARKER")]
+        require_marker: bool,
     },
 
     /// Edit the arkenfox user-overrides.js with an editor
@@ -49,17 +318,153 @@ pub enum Command {
         #[clap(short, long, env = "ARKENCRAB_EDIT_NO_APPLY")]
         no_apply: bool,
 
-        /// The editor to open user-overrides.js with
-        #[clap(short, long, env = "EDITOR")]
+        /// The editor to open user-overrides.js with; defaults to $VISUAL, then $EDITOR
+        #[clap(short, long)]
         editor: Option<String>,
+
+        /// Keep running after the editor is closed, reapplying overrides whenever user-overrides.js changes
+        #[clap(short, long, conflicts_with = "no_apply")]
+        watch: bool,
+
+        /// Print the overrides block of the new user.js after applying
+        #[clap(short, long, conflicts_with = "no_apply")]
+        overrides_only: bool,
+
+        /// Wait for user-overrides.js to be saved (or Enter to be pressed) after the editor exits; enabled automatically for GUI editors that return immediately
+        #[clap(long, env = "ARKENCRAB_EDIT_WAIT")]
+        wait: bool,
+
+        /// The maximum number of seconds to wait for user-overrides.js to be saved
+        #[clap(long, default_value_t = 600, env = "ARKENCRAB_EDIT_WAIT_TIMEOUT")]
+        wait_timeout: u64,
+
+        /// Print the contents of user-overrides.js once editing (and applying) is done
+        #[clap(long)]
+        print_after: bool,
+    },
+
+    /// Check the overrides for known problems
+    Lint,
+
+    /// Export the preferences set by user.js as JSON
+    Export {
+        /// Merge in the values Firefox has materialized in prefs.js, which take precedence
+        #[clap(short, long)]
+        include_defaults: bool,
+
+        /// Only export prefs in this arkenfox section or range of sections (e.g. 0800 or 0800-0900); requires an arkenfox user.js
+        #[clap(long)]
+        section: Option<SectionRange>,
+    },
+
+    /// Back up user.js and/or prefs.js without modifying them
+    Backup {
+        /// The file to back up
+        #[clap(value_enum, default_value_t)]
+        which: FileKind,
     },
 
     /// Print the profile being used
-    Profile {},
+    Profile {
+        /// Print the path to a file inside the profile instead (e.g. user.js, user-overrides.js)
+        #[clap(short, long)]
+        file: Option<PathBuf>,
+    },
+
+    /// Update arkencrab itself to the latest release
+    #[cfg(feature = "self-update")]
+    SelfUpdate {
+        /// Only check for a newer version without installing it
+        #[clap(short = 'n', long)]
+        dry_run: bool,
+    },
 
     /// Generate shell completions
     Completions {
         /// The shell to generate completions for
         shell: clap_complete::Shell,
     },
+
+    /// Install shell completions to the conventional location for the shell
+    InstallCompletions {
+        /// The shell to install completions for
+        shell: clap_complete::Shell,
+
+        /// Overwrite existing completions
+        #[clap(short, long)]
+        force: bool,
+    },
+}
+
+impl Command {
+    /// Whether the command prints its result to stdout, where it would be mixed up with
+    /// `--porcelain` output.
+    pub fn prints_to_stdout(&self) -> bool {
+        match self {
+            Self::Update { list_versions, .. } => *list_versions,
+            Self::Edit { print_after, .. } => *print_after,
+            Self::Export { .. } | Self::Profile { .. } | Self::Completions { .. } => true,
+            _ => false,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Update { .. } => "update",
+            Self::PrefsClean { .. } => "prefs-clean",
+            Self::Edit { .. } => "edit",
+            Self::Lint => "lint",
+            Self::Export { .. } => "export",
+            Self::Backup { .. } => "backup",
+            Self::Profile { .. } => "profile",
+            #[cfg(feature = "self-update")]
+            Self::SelfUpdate { .. } => "self-update",
+            Self::Completions { .. } => "completions",
+            Self::InstallCompletions { .. } => "install-completions",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::{CommandFactory as _, FromArgMatches as _};
+
+    use super::Cli;
+
+    #[test]
+    fn only_one_profile_selector_can_be_passed() -> Result<(), clap::Error> {
+        let matches = Cli::command().try_get_matches_from([
+            "arkencrab",
+            "--profile-index",
+            "0",
+            "profile",
+        ])?;
+        let mut cli = Cli::from_arg_matches(&matches)?;
+        cli.select_profile(&matches)?;
+        assert_eq!(cli.profile_index, Some(0));
+
+        let matches = Cli::command().try_get_matches_from([
+            "arkencrab",
+            "--profile",
+            "a",
+            "profile",
+            "--running",
+        ])?;
+        let mut cli = Cli::from_arg_matches(&matches)?;
+        assert!(cli.select_profile(&matches).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_size_handles_suffixes() {
+        use super::parse_size;
+
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("500K"), Ok(500 * 1024));
+        assert_eq!(parse_size("10M"), Ok(10 * 1024 * 1024));
+        assert_eq!(parse_size("1g"), Ok(1024 * 1024 * 1024));
+        assert!(parse_size("10MB").is_err());
+        assert!(parse_size("M").is_err());
+    }
 }
@@ -0,0 +1,116 @@
+// SPDX-FileCopyrightText: 2025 Ryan Cao <hello@ryanccn.dev>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use clap::CommandFactory as _;
+use clap_complete::Shell;
+use eyre::{Result, bail};
+use owo_colors::OwoColorize as _;
+
+use crate::{cli::Cli, dirs};
+
+/// The conventional location of user completion scripts for a shell.
+fn install_path_in(
+    shell: Shell,
+    home: &Path,
+    data_home: Option<PathBuf>,
+    config_home: Option<PathBuf>,
+) -> Result<PathBuf> {
+    let data_home = data_home.unwrap_or_else(|| home.join(".local").join("share"));
+    let config_home = config_home.unwrap_or_else(|| home.join(".config"));
+
+    Ok(match shell {
+        Shell::Bash => data_home
+            .join("bash-completion")
+            .join("completions")
+            .join("arkencrab"),
+        Shell::Fish => config_home
+            .join("fish")
+            .join("completions")
+            .join("arkencrab.fish"),
+        Shell::Zsh => home.join(".zfunc").join("_arkencrab"),
+        Shell::Elvish => config_home.join("elvish").join("lib").join("arkencrab.elv"),
+        _ => bail!(
+            "{shell} has no conventional location for completions; add the output of `arkencrab completions {shell}` to your profile instead"
+        ),
+    })
+}
+
+fn install_path(shell: Shell) -> Result<PathBuf> {
+    install_path_in(
+        shell,
+        &dirs::home_dir()?,
+        dirs::xdg_dir("XDG_DATA_HOME"),
+        dirs::xdg_dir("XDG_CONFIG_HOME"),
+    )
+}
+
+pub fn install(shell: Shell, force: bool) -> Result<()> {
+    let path = install_path(shell)?;
+
+    if path.exists() && !force {
+        bail!(
+            "{} already exists; pass --force to overwrite it",
+            path.display()
+        );
+    }
+
+    let mut script = Vec::new();
+    clap_complete::generate(shell, &mut Cli::command(), "arkencrab", &mut script);
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, script)?;
+
+    status!("{} {}", "installed".green(), path.display());
+
+    match shell {
+        Shell::Zsh => status!(
+            "{} make sure {} is in your fpath before compinit runs, e.g. `fpath+=(~/.zfunc)` in .zshrc",
+            "note:".dimmed(),
+            path.parent().unwrap_or(&path).display()
+        ),
+        Shell::Elvish => status!(
+            "{} load the completions with `use arkencrab` in rc.elv",
+            "note:".dimmed()
+        ),
+        _ => {}
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use clap_complete::Shell;
+    use eyre::Result;
+
+    #[test]
+    fn install_paths_follow_conventions() -> Result<()> {
+        let home = Path::new("/home/user");
+
+        assert_eq!(
+            super::install_path_in(Shell::Bash, home, None, None)?,
+            home.join(".local/share/bash-completion/completions/arkencrab")
+        );
+        assert_eq!(
+            super::install_path_in(Shell::Fish, home, None, Some(PathBuf::from("/xdg/config")))?,
+            Path::new("/xdg/config/fish/completions/arkencrab.fish")
+        );
+        assert_eq!(
+            super::install_path_in(Shell::Zsh, home, None, None)?,
+            home.join(".zfunc/_arkencrab")
+        );
+        assert!(super::install_path_in(Shell::PowerShell, home, None, None).is_err());
+
+        Ok(())
+    }
+}
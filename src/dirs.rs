@@ -0,0 +1,46 @@
+// SPDX-FileCopyrightText: 2025 Ryan Cao <hello@ryanccn.dev>
+// SPDX-FileCopyrightText: 2025 Seth Flynn <getchoo@tuta.io>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{env, path::PathBuf};
+
+use eyre::{OptionExt as _, Result};
+
+// `env::home_dir` stabilized in latest Rust but not in Nixpkgs Rust, so we implement
+// a knockoff version ourselves.
+pub fn home_dir() -> Result<PathBuf> {
+    Ok(PathBuf::from(
+        env::var_os("HOME").ok_or_eyre("could not obtain home directory")?,
+    ))
+}
+
+#[cfg(windows)]
+pub fn roaming_appdata() -> Result<PathBuf> {
+    let appdata = env::var_os("APPDATA").ok_or_eyre("could not obtain APPDATA directory")?;
+    Ok(PathBuf::from(appdata))
+}
+
+/// Read a base directory from an environment variable, ignoring relative paths as the XDG
+/// Base Directory specification requires.
+pub fn xdg_dir(var: &str) -> Option<PathBuf> {
+    env::var_os(var)
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+}
+
+/// The per-user cache directory, which other users cannot write to.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn cache_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_CACHE_HOME").or_else(|| Some(home_dir().ok()?.join(".cache")))
+}
+
+#[cfg(target_os = "macos")]
+pub fn cache_dir() -> Option<PathBuf> {
+    Some(home_dir().ok()?.join("Library").join("Caches"))
+}
+
+#[cfg(windows)]
+pub fn cache_dir() -> Option<PathBuf> {
+    env::var_os("LOCALAPPDATA").map(PathBuf::from)
+}
@@ -0,0 +1,22 @@
+// SPDX-FileCopyrightText: 2025 Ryan Cao <hello@ryanccn.dev>
+//
+// SPDX-License-Identifier: CC0-1.0
+
+/*** [SECTION 0800]: LOCATION BAR / SEARCH BAR / SUGGESTIONS / HISTORY / FORMS ***/
+user_pref("_user.js.parrot", "0800 syntax error: the parrot's ceased to be!");
+/* 0804: disable live search suggestions ***/
+user_pref("browser.search.suggest.enabled", false);
+
+/* ESR128.x still uses all the following prefs
+// [NOTE] replace the * with a slash in the line above to re-enable active ones
+// FF129+
+user_pref("browser.urlbar.suggest.quicksuggest.sponsored", false); // 0807
+// ***/
+
+/*ESR115.x still uses all the following prefs
+user_pref("network.http.referer.XOriginTrimmingPolicy", 2); // 1602
+// ***/
+
+/** ESR140.x still uses all the following prefs
+user_pref("privacy.query_stripping.enabled", true); // 6510
+// ***/
@@ -0,0 +1,58 @@
+// SPDX-FileCopyrightText: 2025 Ryan Cao <hello@ryanccn.dev>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    io,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+extern "C" fn handle_sigint(_: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::Relaxed);
+}
+
+#[cfg(windows)]
+unsafe extern "system" fn handle_ctrl(ctrl_type: u32) -> windows_sys::core::BOOL {
+    use windows_sys::Win32::System::Console::CTRL_C_EVENT;
+
+    if ctrl_type == CTRL_C_EVENT {
+        INTERRUPTED.store(true, Ordering::Relaxed);
+        1
+    } else {
+        0
+    }
+}
+
+/// Stop Ctrl-C from terminating the process and record it instead, so that a long-running
+/// command can finish what it is doing and stop on its own by checking [`interrupted`].
+pub fn catch() -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        let handler = handle_sigint as extern "C" fn(libc::c_int);
+
+        // SAFETY: the handler only stores to an atomic, which is async-signal-safe.
+        if unsafe { libc::signal(libc::SIGINT, handler as libc::sighandler_t) } == libc::SIG_ERR {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    #[cfg(windows)]
+    {
+        use windows_sys::Win32::System::Console::SetConsoleCtrlHandler;
+
+        // SAFETY: the handler only stores to an atomic.
+        if unsafe { SetConsoleCtrlHandler(Some(handle_ctrl), 1) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether Ctrl-C has been pressed since [`catch`] was called.
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
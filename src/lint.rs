@@ -0,0 +1,154 @@
+// SPDX-FileCopyrightText: 2025 Ryan Cao <hello@ryanccn.dev>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+
+use crate::prefs;
+
+/// A pref, optionally with the JSON literal of the value it has to be set to in order to
+/// take part in a conflict (any value if `None`).
+type PrefMatcher = (&'static str, Option<&'static str>);
+
+/// Combinations of overrides that are known not to work together.
+static CONFLICTS: &[(PrefMatcher, PrefMatcher, &str)] = &[
+    (
+        ("privacy.resistFingerprinting", Some("true")),
+        ("privacy.fingerprintingProtection", Some("true")),
+        "FPP is ignored while RFP is enabled",
+    ),
+    (
+        ("privacy.resistFingerprinting", Some("true")),
+        ("privacy.fingerprintingProtection.overrides", None),
+        "FPP overrides do not apply while RFP is enabled",
+    ),
+    (
+        ("browser.startup.page", Some("3")),
+        (
+            "privacy.clearOnShutdown_v2.historyFormDataAndDownloads",
+            Some("true"),
+        ),
+        "resuming the previous session requires keeping history on shutdown",
+    ),
+    (
+        ("browser.startup.page", Some("3")),
+        ("privacy.clearOnShutdown.history", Some("true")),
+        "resuming the previous session requires keeping history on shutdown",
+    ),
+    (
+        ("network.trr.mode", Some("5")),
+        ("network.trr.uri", None),
+        "the DoH resolver is unused while DoH is disabled",
+    ),
+];
+
+/// A pair of overrides that conflict with each other.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub first: &'static str,
+    pub second: &'static str,
+    pub reason: &'static str,
+}
+
+fn matches(values: &BTreeMap<String, Value>, (key, expected): PrefMatcher) -> bool {
+    values.get(key).is_some_and(|value| {
+        expected.is_none_or(|expected| {
+            serde_json::from_str::<Value>(expected).ok().as_ref() == Some(value)
+        })
+    })
+}
+
+/// Find known conflicting combinations among the values of a set of overrides.
+pub fn conflicts(values: &BTreeMap<String, Value>) -> Vec<Conflict> {
+    CONFLICTS
+        .iter()
+        .filter(|(first, second, _)| matches(values, *first) && matches(values, *second))
+        .map(|(first, second, reason)| Conflict {
+            first: first.0,
+            second: second.0,
+            reason,
+        })
+        .collect()
+}
+
+/// An override whose value is not a literal that Firefox accepts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidValue {
+    /// The 1-based line number of the override.
+    pub line: usize,
+    pub key: String,
+    pub value: String,
+}
+
+/// Whether a value token is a boolean, an integer, or a double- or single-quoted string.
+fn is_valid_value(value: &str) -> bool {
+    let digits = value.strip_prefix(['-', '+']).unwrap_or(value);
+    let is_quoted = |quote| value.len() >= 2 && value.starts_with(quote) && value.ends_with(quote);
+
+    matches!(value, "true" | "false")
+        || (!digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()))
+        || is_quoted('"')
+        || is_quoted('\'')
+}
+
+/// Find overrides with values that Firefox would fail to parse, such as misspelled
+/// booleans or unquoted strings.
+pub fn invalid_values(js: &str) -> Vec<InvalidValue> {
+    js.lines()
+        .enumerate()
+        .filter_map(|(idx, line)| {
+            let (key, value) = prefs::key_value(line)?;
+
+            (!is_valid_value(value)).then(|| InvalidValue {
+                line: idx + 1,
+                key: prefs::unquote(key),
+                value: value.to_owned(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prefs;
+
+    #[test]
+    fn finds_invalid_values() {
+        let overrides = r#"user_pref("a", true);
+user_pref("b", ture);
+user_pref("c", -1);
+user_pref("d", 1.5);
+user_pref("e", "string");
+user_pref("f", unquoted);
+user_pref("g", 'single');
+"#;
+
+        let invalid = super::invalid_values(overrides);
+
+        assert_eq!(
+            invalid
+                .iter()
+                .map(|v| (v.line, v.key.as_str(), v.value.as_str()))
+                .collect::<Vec<_>>(),
+            [(2, "b", "ture"), (4, "d", "1.5"), (6, "f", "unquoted")]
+        );
+    }
+
+    #[test]
+    fn finds_conflicting_overrides() {
+        let overrides = r#"
+user_pref("privacy.resistFingerprinting", true);
+user_pref("privacy.fingerprintingProtection", true);
+user_pref("browser.startup.page", 1);
+user_pref("privacy.clearOnShutdown_v2.historyFormDataAndDownloads", true);
+"#;
+
+        let conflicts = super::conflicts(&prefs::values(overrides));
+
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].first, "privacy.resistFingerprinting");
+        assert_eq!(conflicts[0].second, "privacy.fingerprintingProtection");
+    }
+}
@@ -4,20 +4,59 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 use std::{
-    borrow::Cow, collections::HashSet, env, fs, io, path::Path, process::Command as StdCommand,
-    sync::LazyLock,
+    borrow::Cow,
+    cmp,
+    collections::HashSet,
+    env, fs,
+    io::{self, IsTerminal as _},
+    path::{self, Path, PathBuf},
+    process::Command as StdCommand,
+    sync::{
+        LazyLock,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant, SystemTime},
 };
 
-use anstream::{print, println};
-use clap::{CommandFactory as _, Parser};
-use eyre::{Result, bail, eyre};
+use anstream::{eprint, eprintln, print, println};
+use clap::{CommandFactory as _, FromArgMatches as _};
+use eyre::{OptionExt as _, Result, bail, eyre};
 use owo_colors::OwoColorize as _;
 use regex::{Regex, RegexBuilder};
 
-use crate::cli::{Cli, Command};
+use crate::{
+    cli::{Cli, Command, PathDisplay},
+    prefs::SectionRange,
+    report::Report,
+};
+
+/// Print human-readable status output, which is moved to stderr when `--porcelain` output
+/// has been requested on stdout.
+macro_rules! status {
+    ($($arg:tt)*) => {
+        if $crate::PORCELAIN.load(::std::sync::atomic::Ordering::Relaxed) {
+            ::anstream::eprintln!($($arg)*);
+        } else {
+            ::anstream::println!($($arg)*);
+        }
+    };
+}
 
 mod cli;
+mod completions;
+mod dirs;
+mod interrupt;
+mod lint;
+mod overrides;
+mod prefs;
 mod profiles;
+mod report;
+#[cfg(feature = "self-update")]
+mod self_update;
+mod userjs;
+mod versions;
+
+static PORCELAIN: AtomicBool = AtomicBool::new(false);
 
 static USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
 
@@ -26,6 +65,8 @@ static DEFAULT_EDITOR: &str = "nano";
 #[cfg(windows)]
 static DEFAULT_EDITOR: &str = "notepad.exe";
 
+static OVERRIDES_TEMPLATE: &str = include_str!("user-overrides.template.js");
+
 static ARKENCRAB_START_MARKER: &str = "/** START: arkencrab overrides */";
 
 static REGEX_VERSION: LazyLock<Regex> = LazyLock::new(|| {
@@ -35,24 +76,63 @@ static REGEX_VERSION: LazyLock<Regex> = LazyLock::new(|| {
         .unwrap()
 });
 
-static REGEX_USER_PREF: LazyLock<Regex> = LazyLock::new(|| {
-    RegexBuilder::new(r#"^\s*user_pref\((".*?"),"#)
-        .multi_line(true)
-        .build()
-        .unwrap()
-});
-
-fn resolve_profile(cli: &'_ Cli) -> Result<Cow<'_, Path>> {
+fn resolve_profile(cli: &Cli) -> Result<PathBuf> {
     let profile = if let Some(p) = &cli.profile {
         Cow::Borrowed(p.as_path())
+    } else if let Some(index) = cli.profile_index {
+        Cow::Owned(profiles::profile_by_index(index)?)
+    } else if cli.running {
+        Cow::Owned(profiles::running_profile()?)
     } else {
-        let profile = profiles::default_profile()?;
+        let profile = profiles::default_profile(cli.verbose)?;
         Cow::Owned(profile)
     };
 
+    // Relative paths are resolved against the current directory; fall back to a purely
+    // lexical resolution if the directory does not exist (yet).
+    let profile = match fs::canonicalize(&profile) {
+        Ok(profile) => strip_verbatim_prefix(profile),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => path::absolute(&profile)?,
+        Err(err) => return Err(err.into()),
+    };
+
     Ok(profile)
 }
 
+/// Turn a verbatim `\\?\C:\...` path returned by `fs::canonicalize` on Windows back into
+/// the `C:\...` form that users and most programs expect. Verbatim UNC paths are kept.
+#[cfg(windows)]
+fn strip_verbatim_prefix(path: PathBuf) -> PathBuf {
+    use std::path::{Component, Prefix};
+
+    let is_verbatim_disk = matches!(
+        path.components().next(),
+        Some(Component::Prefix(prefix)) if matches!(prefix.kind(), Prefix::VerbatimDisk(_))
+    );
+
+    if is_verbatim_disk && let Some(stripped) = path.to_str().and_then(|p| p.strip_prefix(r"\\?\"))
+    {
+        return PathBuf::from(stripped);
+    }
+
+    path
+}
+
+#[cfg(unix)]
+fn strip_verbatim_prefix(path: PathBuf) -> PathBuf {
+    path
+}
+
+fn warn_if_sandboxed(profile: &Path) {
+    if let Some(sandbox) = profiles::sandbox(profile) {
+        eprintln!(
+            "{} profile belongs to a {sandbox:?} Firefox; {}",
+            "warning:".yellow(),
+            sandbox.advice()
+        );
+    }
+}
+
 fn read_string_with_default(path: impl AsRef<Path>) -> Result<String> {
     match fs::read_to_string(path) {
         Ok(s) => Ok(s),
@@ -61,11 +141,14 @@ fn read_string_with_default(path: impl AsRef<Path>) -> Result<String> {
     }
 }
 
-fn find_version(user_js: &str) -> String {
+fn find_version(user_js: &str) -> Option<String> {
     REGEX_VERSION
         .captures(user_js)
-        .map_or("unknown", |c| c.extract::<1>().1[0])
-        .to_owned()
+        .map(|c| c.extract::<1>().1[0].to_owned())
+}
+
+fn display_version(version: Option<&str>) -> &str {
+    version.unwrap_or("unknown")
 }
 
 fn print_diff(old: &str, new: &str) {
@@ -73,13 +156,12 @@ fn print_diff(old: &str, new: &str) {
 
     let diff = TextDiff::from_lines(old, new);
 
-    print!(
-        "{}",
-        diff.unified_diff()
-            .context_radius(2)
-            .iter_hunks()
-            .map(|hunk| hunk
-                .iter_changes()
+    let diff = diff
+        .unified_diff()
+        .context_radius(2)
+        .iter_hunks()
+        .map(|hunk| {
+            hunk.iter_changes()
                 .map(|change| {
                     let plain = format!("{}\t{}", change.tag(), change);
                     match change.tag() {
@@ -88,85 +170,1072 @@ fn print_diff(old: &str, new: &str) {
                         ChangeTag::Delete => plain.red().to_string(),
                     }
                 })
-                .collect::<String>())
-            .collect::<Vec<_>>()
-            .join("\n")
-    );
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    if PORCELAIN.load(Ordering::Relaxed) {
+        eprint!("{diff}");
+    } else {
+        print!("{diff}");
+    }
+}
+
+/// Write a plain unified diff between two versions of `name` to `path`, in the format of
+/// `diff -u` so that it can be consumed by `patch` and other tools.
+fn write_diff(path: &Path, name: &str, old: &str, new: &str) -> Result<()> {
+    let diff = similar::TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(2)
+        .header(&format!("a/{name}"), &format!("b/{name}"))
+        .to_string();
+
+    fs::write(path, diff)?;
+    status!("{} diff to {}", "saved".magenta(), path.display());
+
+    Ok(())
+}
+
+fn http_client(cli: &Cli) -> Result<reqwest::blocking::Client> {
+    Ok(reqwest::blocking::Client::builder()
+        .https_only(true)
+        .user_agent(cli.user_agent.as_deref().unwrap_or(USER_AGENT))
+        .build()?)
+}
+
+/// The URL of the arkenfox user.js at a branch or tag.
+fn userjs_url(r#ref: &str) -> String {
+    format!("https://raw.githubusercontent.com/arkenfox/user.js/{ref}/user.js")
+}
+
+fn section_keys(user: &str, section: SectionRange) -> Result<HashSet<&str>> {
+    prefs::keys_in_sections(user, section).ok_or_eyre(
+        "user.js has no arkenfox sections to filter by; run `arkencrab update` to fetch the arkenfox user.js",
+    )
+}
+
+/// Ask the user a yes/no question on stderr, defaulting to no.
+fn confirm(question: &str) -> Result<bool> {
+    eprint!("{question} {} ", "[y/N]".dimmed());
+
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
 }
 
 fn now() -> String {
     chrono::Local::now().format("%Y-%m-%d-%H-%M-%S").to_string()
 }
 
-fn main() -> Result<()> {
-    color_eyre::install()?;
+/// Back up `contents` of the profile file `name` to a timestamped file in its backup
+/// directory (e.g. `userjs_backups` for `user.js`), returning the path of the backup
+/// relative to the profile.
+fn backup_file(cli: &Cli, profile: &Path, name: &str, contents: &str) -> Result<PathBuf> {
+    backup_file_in(
+        cli,
+        profile,
+        &format!("{}_backups", name.replace('.', "")),
+        name,
+        contents,
+    )
+}
+
+/// Back up `contents` of the profile file `name` to a timestamped file in the backup
+/// directory `dir` of the profile, returning the path of the backup relative to the profile.
+fn backup_file_in(
+    cli: &Cli,
+    profile: &Path,
+    dir: &str,
+    name: &str,
+    contents: &str,
+) -> Result<PathBuf> {
+    let dir = PathBuf::from(dir);
+    let backup = dir.join(format!("{name}.backup.{}", now()));
+
+    fs::create_dir_all(profile.join(&dir))?;
+    fs::write(profile.join(&backup), contents)?;
+
+    status!(
+        "{} {name} to {}",
+        "backed up".magenta(),
+        display_path(cli, profile, &profile.join(&backup)).display()
+    );
+
+    if cli.latest_backup_link {
+        link_latest_backup(&profile.join(&dir), name, &backup)?;
+    }
+
+    if let Some(max_size) = cli.max_backups_size {
+        for removed in prune_backups_by_size(&profile.join(&dir), name, max_size)? {
+            status!(
+                "{} old backup {}",
+                "removed".red(),
+                display_path(cli, profile, &removed).display()
+            );
+        }
+    }
+
+    Ok(backup)
+}
+
+/// Point `<name>.backup.latest` in `dir` at `backup`. The link is relative so that it
+/// survives moving the profile; where symlinks cannot be created, a pointer file containing
+/// the file name of the backup is written instead.
+fn link_latest_backup(dir: &Path, name: &str, backup: &Path) -> Result<()> {
+    let latest = dir.join(format!("{name}.backup.latest"));
+    let target = backup.file_name().ok_or_eyre("backup has no file name")?;
+
+    if latest.symlink_metadata().is_ok() {
+        fs::remove_file(&latest)?;
+    }
+
+    #[cfg(unix)]
+    let linked = std::os::unix::fs::symlink(target, &latest);
+    #[cfg(windows)]
+    let linked = std::os::windows::fs::symlink_file(target, &latest);
+
+    if linked.is_err() {
+        fs::write(&latest, target.as_encoded_bytes())?;
+    }
+
+    Ok(())
+}
+
+/// Delete the oldest backups of `name` in `dir` until their total size is at most
+/// `max_size` bytes, returning the paths of the deleted backups. The newest backup is always
+/// kept, even if it alone exceeds the cap.
+fn prune_backups_by_size(dir: &Path, name: &str, max_size: u64) -> Result<Vec<PathBuf>> {
+    let prefix = format!("{name}.backup.");
+    let latest = format!("{name}.backup.latest");
+    let mut backups = Vec::new();
+
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_name = entry.file_name();
+        let file_name = file_name.to_string_lossy();
+
+        if file_name.starts_with(&prefix) && file_name != latest && entry.file_type()?.is_file() {
+            backups.push((entry.path(), entry.metadata()?.len()));
+        }
+    }
+
+    // Backup names end in a sortable timestamp, so this orders them from oldest to newest.
+    backups.sort_by(|(a, _), (b, _)| a.file_name().cmp(&b.file_name()));
+
+    let mut total = backups.iter().map(|(_, size)| size).sum::<u64>();
+    let mut removed = Vec::new();
+
+    for (path, size) in backups.iter().take(backups.len().saturating_sub(1)) {
+        if total <= max_size {
+            break;
+        }
+
+        fs::remove_file(path)?;
+        total -= size;
+        removed.push(path.clone());
+    }
+
+    Ok(removed)
+}
+
+/// Express the absolute `path` relative to the absolute directory `base`, keeping it
+/// absolute if they do not share a root (e.g. on different drives).
+fn relative_path(path: &Path, base: &Path) -> PathBuf {
+    let path_components = path.components().collect::<Vec<_>>();
+    let base_components = base.components().collect::<Vec<_>>();
+
+    let common = path_components
+        .iter()
+        .zip(&base_components)
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    if common == 0 {
+        return path.to_owned();
+    }
+
+    let mut relative = PathBuf::new();
+    for _ in common..base_components.len() {
+        relative.push("..");
+    }
+    relative.extend(&path_components[common..]);
+
+    if relative.as_os_str().is_empty() {
+        relative.push(".");
+    }
+
+    relative
+}
+
+/// Format the absolute path of the profile or a file in it for output according to
+/// `--relative-to`. The profile itself is printed in full when printing relative to it.
+fn display_path(cli: &Cli, profile: &Path, path: &Path) -> PathBuf {
+    match cli.relative_to {
+        PathDisplay::Profile => path
+            .strip_prefix(profile)
+            .ok()
+            .filter(|p| !p.as_os_str().is_empty())
+            .map_or_else(|| path.to_owned(), Path::to_owned),
+        PathDisplay::Cwd => {
+            env::current_dir().map_or_else(|_| path.to_owned(), |cwd| relative_path(path, &cwd))
+        }
+        PathDisplay::Absolute => path.to_owned(),
+    }
+}
+
+/// Refuse to go on if `path` is a symlink, unless `--follow-symlink` or `--replace-symlink`
+/// says what to do with it. Nothing is changed here; a symlink is only replaced right before
+/// it is written to.
+fn check_symlink(cli: &Cli, path: &Path) -> Result<()> {
+    if !path.is_symlink() {
+        return Ok(());
+    }
+
+    let target = fs::read_link(path)?;
+
+    if cli.follow_symlink {
+        eprintln!(
+            "{} {} is a symlink; writing through it to {}",
+            "warning:".yellow(),
+            path.display(),
+            target.display()
+        );
+    } else if !cli.replace_symlink {
+        bail!(
+            "{} is a symlink to {}; pass `--follow-symlink` to write through it or `--replace-symlink` to replace it with a regular file",
+            path.display(),
+            target.display()
+        );
+    }
+
+    Ok(())
+}
+
+/// With `--replace-symlink`, replace the symlink at `path` with a regular file holding a
+/// copy of what it pointed to.
+fn replace_symlink(cli: &Cli, path: &Path) -> Result<()> {
+    if !cli.replace_symlink || !path.is_symlink() {
+        return Ok(());
+    }
+
+    let target = fs::read_link(path)?;
+    let contents = match fs::read(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(err) => return Err(err.into()),
+    };
+
+    fs::remove_file(path)?;
+    fs::write(path, contents)?;
+
+    status!(
+        "{} symlink {} with a copy of {}",
+        "replaced".magenta(),
+        path.display(),
+        target.display()
+    );
+
+    Ok(())
+}
+
+/// Write `contents` to the profile file `name`, which must already have been checked with
+/// [`check_symlink`] before anything was backed up or written. The contents are written to a
+/// temporary file next to it that is then renamed into place, so that a failed write leaves
+/// the existing file untouched. With `--verify-after-write`, the file is read back and, if it
+/// does not match, restored from `backup` (relative to the profile).
+fn write_profile_file(
+    cli: &Cli,
+    profile: &Path,
+    name: &str,
+    contents: &str,
+    backup: &Path,
+) -> Result<()> {
+    let path = profile.join(name);
+
+    // Renaming over a symlink replaces the symlink itself, which is what `--replace-symlink`
+    // asks for; otherwise the file it points to is replaced instead.
+    let replacing_symlink = cli.replace_symlink && path.is_symlink();
+    let target = if path.is_symlink() && !replacing_symlink {
+        fs::canonicalize(&path)
+            .or_else(|_| -> io::Result<PathBuf> { Ok(profile.join(fs::read_link(&path)?)) })?
+    } else {
+        path.clone()
+    };
+
+    let staged = target.with_file_name(format!(
+        "{}.arkencrab-tmp",
+        target
+            .file_name()
+            .map_or_else(|| name.into(), |n| n.to_string_lossy())
+    ));
+
+    let written = fs::write(&staged, contents)
+        .and_then(|()| match fs::metadata(&target) {
+            Ok(metadata) => fs::set_permissions(&staged, metadata.permissions()),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err),
+        })
+        .and_then(|()| fs::rename(&staged, &target));
+
+    if let Err(err) = written {
+        let _ = fs::remove_file(&staged);
+        bail!(
+            "failed to write {name}: {err}; {name} was left unchanged and a backup of it is at {}",
+            display_path(cli, profile, &profile.join(backup)).display()
+        );
+    }
+
+    if replacing_symlink {
+        status!(
+            "{} symlink {} with a regular file",
+            "replaced".magenta(),
+            path.display()
+        );
+    }
+
+    if cli.verify_after_write && fs::read(&path)? != contents.as_bytes() {
+        fs::copy(profile.join(backup), &path)?;
+        bail!(
+            "{name} did not match what was written; restored it from {}",
+            display_path(cli, profile, &profile.join(backup)).display()
+        );
+    }
+
+    Ok(())
+}
+
+fn find_executable(program: &str) -> Option<PathBuf> {
+    #[cfg(unix)]
+    fn is_executable(path: &Path) -> bool {
+        use std::os::unix::fs::PermissionsExt as _;
+        path.metadata()
+            .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+    }
+
+    #[cfg(windows)]
+    fn is_executable(path: &Path) -> bool {
+        path.is_file()
+    }
+
+    let program = Path::new(program);
+
+    if program.components().count() > 1 {
+        return is_executable(program).then(|| program.to_owned());
+    }
+
+    #[cfg(unix)]
+    let extensions = [String::new()];
+    #[cfg(windows)]
+    let extensions = std::iter::once(String::new())
+        .chain(
+            env::var("PATHEXT")
+                .unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".to_owned())
+                .split(';')
+                .map(|e| e.to_owned()),
+        )
+        .collect::<Vec<_>>();
+
+    env::split_paths(&env::var_os("PATH")?).find_map(|dir| {
+        extensions.iter().find_map(|ext| {
+            let mut candidate = dir.join(program).into_os_string();
+            candidate.push(ext);
+            let candidate = PathBuf::from(candidate);
+            is_executable(&candidate).then_some(candidate)
+        })
+    })
+}
+
+fn print_overrides(overrides: &str) {
+    for line in overrides.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("//") || trimmed.starts_with("/*") || trimmed.starts_with('*') {
+            status!("{}", line.dimmed());
+        } else {
+            status!("{}", line.green());
+        }
+    }
+}
+
+/// Move prefs that were appended to user.js by hand to the end of user-overrides.js.
+fn migrate_manual_overrides(cli: &Cli, profile: &Path, manual: &str) -> Result<()> {
+    check_symlink(cli, &profile.join("user-overrides.js"))?;
+
+    let existing = read_string_with_default(profile.join("user-overrides.js"))?;
+    // The migration is part of updating user.js, so user-overrides.js is backed up along
+    // with it rather than to a backup directory of its own.
+    let backup = backup_file_in(
+        cli,
+        profile,
+        "userjs_backups",
+        "user-overrides.js",
+        &existing,
+    )?;
+
+    let mut overrides = existing.trim_end().to_owned();
+    if !overrides.is_empty() {
+        overrides += "\n\n";
+    }
+    overrides += manual.trim();
+    overrides += "\n";
+
+    write_profile_file(cli, profile, "user-overrides.js", &overrides, &backup)?;
+
+    status!(
+        "{} {} prefs from user.js to user-overrides.js",
+        "migrated".magenta(),
+        prefs::keys(manual).count()
+    );
+
+    Ok(())
+}
+
+/// Render the `--overrides-header` for the given arkenfox version, if any.
+fn overrides_header(cli: &Cli, version: Option<&str>) -> Option<String> {
+    cli.overrides_header.as_deref().map(|template| {
+        userjs::render_header(
+            template,
+            display_version(version),
+            &chrono::Local::now().format("%Y-%m-%d").to_string(),
+        )
+    })
+}
+
+fn read_overrides(cli: &Cli, profile: &Path) -> Result<String> {
+    Ok(read_checked_overrides(cli, profile)?.0)
+}
+
+/// Read the overrides of a profile, warning about values that Firefox would fail to parse
+/// (which makes it stop reading user.js altogether) and returning how many there were.
+fn read_checked_overrides(cli: &Cli, profile: &Path) -> Result<(String, usize)> {
+    let overrides = overrides::read(
+        profile,
+        cli.overrides_dir.as_deref(),
+        profiles::profile_name(profile).as_deref(),
+        cli.merge_strategy,
+    )?;
+
+    if cli.verbose {
+        for source in &overrides.sources {
+            status!("{} {}", "including".dimmed(), source.display());
+        }
+    }
+
+    let mut invalid_values = 0;
+
+    for path in overrides.sources {
+        invalid_values += warn_invalid_values(&path, &read_string_with_default(&path)?);
+    }
+
+    Ok((overrides.content, invalid_values))
+}
+
+/// Warn about override values in `source` that Firefox would fail to parse, returning how
+/// many there were.
+fn warn_invalid_values(path: &Path, source: &str) -> usize {
+    let invalid_values = lint::invalid_values(source);
+
+    for invalid in &invalid_values {
+        eprintln!(
+            "{} invalid value {} for {} at {}:{}",
+            "warning:".yellow(),
+            invalid.value.red(),
+            invalid.key.cyan(),
+            path.display(),
+            invalid.line
+        );
+    }
+
+    invalid_values.len()
+}
+
+/// Read overrides piped to standard input in place of the overrides of the profile.
+fn read_stdin_overrides() -> Result<String> {
+    let overrides = overrides::read_from(io::stdin().lock())?;
+    warn_invalid_values(Path::new("<stdin>"), &overrides);
+
+    Ok(overrides)
+}
+
+/// Run a `--hook` command after an update, describing the result in its environment.
+fn run_hook(hook: &str, report: &Report) -> Result<()> {
+    let mut hook = shlex::split(hook)
+        .filter(|h| !h.is_empty())
+        .ok_or_else(|| eyre!("invalid hook provided"))?;
+    let program = hook.remove(0);
+
+    let mut command = StdCommand::new(&program);
+    command.args(&hook).envs([
+        (
+            "ARKENCRAB_OLD_VERSION",
+            report.old_version.clone().unwrap_or_default(),
+        ),
+        (
+            "ARKENCRAB_NEW_VERSION",
+            report.new_version.clone().unwrap_or_default(),
+        ),
+    ]);
+
+    for (name, path) in [
+        ("ARKENCRAB_PROFILE", &report.profile),
+        ("ARKENCRAB_BACKUP", &report.backup),
+    ] {
+        command.env(name, path.as_deref().unwrap_or_else(|| Path::new("")));
+    }
+
+    status!("{} hook {}", "running".blue(), program);
+
+    let status = command
+        .status()
+        .map_err(|err| eyre!("failed to run hook `{program}`: {err}"))?;
+
+    if !status.success() {
+        bail!("hook failed with status code {:?}", status.code());
+    }
+
+    Ok(())
+}
+
+/// Whether an editor is known to return immediately after handing the file off to an
+/// already running or newly forked GUI process, unless told to wait.
+fn is_detaching_editor(program: &str, args: &[String]) -> bool {
+    let name = Path::new(program)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or(program);
+
+    let waits = args
+        .iter()
+        .any(|a| matches!(a.as_str(), "-w" | "--wait" | "-f" | "--nofork"));
+
+    !waits
+        && matches!(
+            name,
+            "code"
+                | "code-insiders"
+                | "codium"
+                | "subl"
+                | "zed"
+                | "atom"
+                | "gvim"
+                | "mvim"
+                | "open"
+        )
+}
+
+/// Wait until `path` is modified after `since`, the user presses Enter, or `timeout` elapses.
+fn wait_for_edit(path: &Path, since: Option<SystemTime>, timeout: Duration) -> Result<()> {
+    use std::{sync::mpsc, thread};
+
+    status!(
+        "{} for {} to be saved; press {} when done",
+        "waiting".blue(),
+        path.display(),
+        "Enter".cyan()
+    );
+
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        if io::stdin()
+            .read_line(&mut String::new())
+            .is_ok_and(|n| n > 0)
+        {
+            let _ = tx.send(());
+        }
+    });
+
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        let modified = fs::metadata(path).and_then(|m| m.modified()).ok();
+        if modified.is_some() && modified != since {
+            return Ok(());
+        }
+
+        if Instant::now() >= deadline {
+            bail!(
+                "timed out after {}s waiting for {} to be saved; overrides were not applied",
+                timeout.as_secs(),
+                path.display()
+            );
+        }
+
+        match rx.recv_timeout(Duration::from_millis(250)) {
+            Ok(()) => return Ok(()),
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            // stdin was closed, so only a change to the file can end the wait
+            Err(mpsc::RecvTimeoutError::Disconnected) => thread::sleep(Duration::from_millis(250)),
+        }
+    }
+}
+
+fn apply_overrides(
+    cli: &Cli,
+    profile: &Path,
+    report: &mut Report,
+    show_overrides: bool,
+) -> Result<()> {
+    let existing_user = read_string_with_default(profile.join("user.js"))?;
+    let existing_version = find_version(&existing_user);
+
+    let mut new_user = match userjs::reapply_base(&existing_user) {
+        userjs::Reapply::MissingBase => {
+            status!(
+                "{} arkenfox user.js to apply overrides to; run {} to install it along with your overrides",
+                "found no".yellow(),
+                "`arkencrab update`".cyan()
+            );
+            return Ok(());
+        }
+        userjs::Reapply::Unmanaged => {
+            status!(
+                "{} automatically update user.js with new overrides; run {}",
+                "could not".yellow(),
+                "`arkencrab update`".cyan()
+            );
+            return Ok(());
+        }
+        userjs::Reapply::Base(base) => base,
+    };
+
+    let overrides = read_overrides(cli, profile)?;
+    let header = overrides_header(cli, existing_version.as_deref());
+    new_user = userjs::append_overrides(&new_user, header.as_deref(), &overrides);
+
+    report.old_version.clone_from(&existing_version);
+    report.new_version.clone_from(&existing_version);
+    report.changed = Some(existing_user != new_user);
+
+    if existing_user == new_user && !cli.keep_backup_on_unchanged {
+        status!("{} overrides, nothing to do", "unchanged".dimmed());
+    } else {
+        let backup = backup_file(cli, profile, "user.js", &existing_user)?;
+        report.backup = Some(profile.join(&backup));
+
+        write_profile_file(cli, profile, "user.js", &new_user, &backup)?;
+
+        status!(
+            "{} arkenfox v{} with new overrides",
+            "updated".green(),
+            display_version(existing_version.as_deref()).green(),
+        );
+    }
+
+    if show_overrides
+        && let (_, Some(overrides)) =
+            userjs::split_overrides(&read_string_with_default(profile.join("user.js"))?)
+    {
+        print_overrides(&overrides);
+    }
+
+    Ok(())
+}
+
+fn watch_overrides(
+    cli: &Cli,
+    profile: &Path,
+    report: &mut Report,
+    show_overrides: bool,
+) -> Result<()> {
+    use notify::{EventKind, RecursiveMode, Watcher as _};
+    use std::sync::mpsc;
+
+    let overrides = profile.join("user-overrides.js");
+    let fragments = profile.join("user-overrides.d");
+    let shared = cli.overrides_dir.as_deref().filter(|dir| dir.is_dir());
+    let profile_specific =
+        profiles::profile_name(profile).map(|name| overrides::profile_file_name(&name));
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    // Editors commonly save by writing a temporary file and renaming it over the original,
+    // so we watch the profile directory rather than the file itself.
+    watcher.watch(profile, RecursiveMode::NonRecursive)?;
+    if fragments.is_dir() {
+        watcher.watch(&fragments, RecursiveMode::NonRecursive)?;
+    }
+    if let Some(shared) = shared {
+        watcher.watch(shared, RecursiveMode::NonRecursive)?;
+    }
 
-    let cli = Cli::parse();
+    status!(
+        "{} {} for changes; press {} to stop",
+        "watching".blue(),
+        overrides.display(),
+        "Ctrl-C".cyan()
+    );
+
+    let is_relevant = |event: &notify::Result<notify::Event>| {
+        event.as_ref().is_ok_and(|event| {
+            matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_))
+                && event.paths.iter().any(|p| {
+                    p.file_name() == overrides.file_name()
+                        || p.parent() == Some(fragments.as_path())
+                        || profile_specific
+                            .as_deref()
+                            .is_some_and(|name| p.file_name() == Some(name.as_ref()))
+                })
+        })
+    };
+
+    // Ctrl-C only stops the loop, so that an update in progress is never cut short and the
+    // report is still written once watching stops.
+    interrupt::catch()?;
+
+    while !interrupt::interrupted() {
+        let event = match rx.recv_timeout(Duration::from_millis(250)) {
+            Ok(event) => event,
+            Err(mpsc::RecvTimeoutError::Timeout) => continue,
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        };
+
+        if let Err(err) = &event {
+            eprintln!("{} {err}", "watch error".red());
+        }
+
+        if !is_relevant(&event) {
+            continue;
+        }
+
+        // A single save usually produces a burst of events; wait for it to settle.
+        while rx.recv_timeout(Duration::from_millis(200)).is_ok() {}
+
+        // Keep watching so that the overrides can be fixed and saved again.
+        if let Err(err) = apply_overrides(cli, profile, report, show_overrides) {
+            eprintln!("{} {err:#}", "error:".red());
+        }
+    }
 
-    match &cli.command {
+    status!("{} watching", "stopped".dimmed());
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_lines)]
+fn run(cli: &Cli, command: &Command, report: &mut Report) -> Result<()> {
+    match command {
         Command::Update {
             diff,
             no_overrides,
             r#ref,
             esr,
+            strip_comments,
+            force,
+            fail_on_change,
+            diff_output,
+            explain,
+            yes,
+            list_versions,
+            json,
+            hook,
+            stdin_overrides,
         } => {
-            let profile = resolve_profile(&cli)?;
-            println!("{} {}", "using profile".blue(), profile.display());
+            if *stdin_overrides && *explain && !yes {
+                bail!(
+                    "`--explain` reads its confirmation from standard input, which `--stdin-overrides` uses for the overrides; pass `--yes` as well"
+                );
+            }
+
+            if *list_versions {
+                let http = http_client(cli)?;
+
+                let master = http
+                    .get(userjs_url("master"))
+                    .send()
+                    .and_then(|r| r.error_for_status())
+                    .and_then(|r| r.text())
+                    .ok();
+                let versions =
+                    versions::list(&http, master.as_deref().and_then(find_version).as_deref())?;
+
+                if *json {
+                    println!("{}", serde_json::to_string_pretty(&versions)?);
+                } else {
+                    for version in &versions {
+                        if version.master {
+                            println!("{} {}", version.tag, "(master)".green());
+                        } else {
+                            println!("{}", version.tag);
+                        }
+                    }
+                }
+
+                return Ok(());
+            }
+
+            let profile = resolve_profile(cli)?;
+            status!(
+                "{} {}",
+                "using profile".blue(),
+                display_path(cli, &profile, &profile).display()
+            );
+            warn_if_sandboxed(&profile);
+            report.profile = Some(profile.clone());
+
+            if !fail_on_change {
+                check_symlink(cli, &profile.join("user.js"))?;
+            }
+
+            let url = userjs_url(r#ref);
+
+            if *explain {
+                let mut steps = vec![format!("download the arkenfox user.js from {url}")];
+
+                if !fail_on_change
+                    && let Some(existing_version) =
+                        find_version(&read_string_with_default(profile.join("user.js"))?)
+                {
+                    steps.push(if *force {
+                        format!(
+                            "warn if it is older than the installed arkenfox v{existing_version}, but use it anyway"
+                        )
+                    } else {
+                        format!(
+                            "stop without changing anything if it is older than the installed arkenfox v{existing_version}"
+                        )
+                    });
+                }
+
+                if *esr {
+                    steps.push("enable the prefs arkenfox keeps for Firefox ESR".to_owned());
+                }
+
+                if *no_overrides {
+                    steps.push("leave out your overrides".to_owned());
+                } else if *stdin_overrides {
+                    steps.push(
+                        "append the overrides read from standard input after the arkencrab marker"
+                            .to_owned(),
+                    );
+                } else {
+                    let overrides = overrides::read(
+                        &profile,
+                        cli.overrides_dir.as_deref(),
+                        profiles::profile_name(&profile).as_deref(),
+                        cli.merge_strategy,
+                    )?;
+                    let sources = overrides
+                        .sources
+                        .iter()
+                        .map(|s| s.display().to_string())
+                        .collect::<Vec<_>>();
+
+                    steps.push(format!(
+                        "append your overrides from {} after the arkencrab marker",
+                        sources.join(", ")
+                    ));
+                }
+
+                if *strip_comments {
+                    steps.push("remove comments and blank lines".to_owned());
+                }
+
+                if *fail_on_change {
+                    steps.push(
+                        "compare the result to the existing user.js and fail if it differs, without writing anything"
+                            .to_owned(),
+                    );
+                } else {
+                    steps.push(format!(
+                        "back up the existing user.js to {}",
+                        profile.join("userjs_backups").display()
+                    ));
+                    steps.push(format!(
+                        "replace {} with the result",
+                        profile.join("user.js").display()
+                    ));
+                }
+
+                status!("{} will:", "arkencrab update".cyan());
+                for (i, step) in steps.iter().enumerate() {
+                    status!("  {}. {step}", i + 1);
+                }
+
+                if !yes && !confirm("proceed?")? {
+                    status!("{} nothing was changed", "aborted;".yellow());
+                    return Ok(());
+                }
+            }
 
             let existing_user = read_string_with_default(profile.join("user.js"))?;
             let existing_version = find_version(&existing_user);
 
-            let backup = Path::new("userjs_backups").join(format!("user.js.backup.{}", now()));
+            let http = http_client(cli)?;
 
-            fs::create_dir_all(profile.join("userjs_backups"))?;
-            fs::write(profile.join(&backup), &existing_user)?;
+            let mut new_user = http.get(&url).send()?.error_for_status()?.text()?;
 
-            println!("{} user.js to {}", "backed up".magenta(), backup.display());
+            let this_version = find_version(&new_user);
 
-            let http = reqwest::blocking::Client::builder()
-                .https_only(true)
-                .user_agent(USER_AGENT)
-                .build()?;
+            if this_version.is_none() {
+                if !force {
+                    bail!(
+                        "fetched user.js does not declare an arkenfox version and may not be a valid arkenfox user.js (e.g. an error or login page returned by a proxy); pass `--force` to use it anyway"
+                    );
+                }
 
-            let mut new_user = http
-                .get(format!(
-                    "https://raw.githubusercontent.com/arkenfox/user.js/refs/heads/{ref}/user.js"
-                ))
-                .send()?
-                .error_for_status()?
-                .text()?;
+                eprintln!(
+                    "{} fetched user.js does not declare an arkenfox version",
+                    "warning:".yellow()
+                );
+            }
 
-            let this_version = find_version(&new_user);
+            let ordering =
+                userjs::compare_versions(existing_version.as_deref(), this_version.as_deref());
+
+            if ordering == Some(cmp::Ordering::Greater) && !fail_on_change {
+                if !force {
+                    bail!(
+                        "fetched arkenfox v{} is older than the installed v{}; pass `--force` to downgrade",
+                        display_version(this_version.as_deref()),
+                        display_version(existing_version.as_deref()),
+                    );
+                }
+
+                eprintln!(
+                    "{} downgrading arkenfox from v{} to v{}",
+                    "warning:".yellow(),
+                    display_version(existing_version.as_deref()),
+                    display_version(this_version.as_deref()),
+                );
+            }
+
+            let mut existing_base = userjs::split_overrides(&existing_user).0;
+
+            if !no_overrides
+                && !stdin_overrides
+                && !fail_on_change
+                && let Some((base, manual)) = userjs::manual_overrides(&existing_user, &new_user)
+            {
+                status!(
+                    "{} {} prefs appended to user.js without the arkencrab marker:",
+                    "found".yellow(),
+                    prefs::keys(manual).count()
+                );
+                print_overrides(manual.trim());
+
+                let migrate = if *yes {
+                    true
+                } else if io::stdin().is_terminal() {
+                    confirm("move them to user-overrides.js?")?
+                } else {
+                    eprintln!(
+                        "{} not moving them to user-overrides.js without confirmation; pass `--yes` to move them",
+                        "warning:".yellow()
+                    );
+                    false
+                };
+
+                if migrate {
+                    migrate_manual_overrides(cli, &profile, manual)?;
+                    base.clone_into(&mut existing_base);
+                } else {
+                    status!(
+                        "{} migrating them; they will not be in the updated user.js, but are kept in its backup",
+                        "skipped".dimmed()
+                    );
+                }
+            }
+
+            let dropped_prefs =
+                prefs::removed_keys(&existing_base, &userjs::split_overrides(&new_user).0);
 
             if *esr {
-                new_user = new_user.replace("/* ESR", "// ESR");
+                new_user = userjs::enable_esr(&new_user);
+            }
+
+            if let (base, Some(_)) = userjs::split_overrides(&new_user) {
+                eprintln!(
+                    "{} fetched user.js already contains arkencrab overrides; replacing them",
+                    "warning:".yellow()
+                );
+                new_user = base.trim_end().to_owned() + "\n";
             }
 
             if !no_overrides {
-                let overrides = read_string_with_default(profile.join("user-overrides.js"))?;
-                new_user += "\n";
-                new_user += ARKENCRAB_START_MARKER;
-                new_user += "\n\n";
-                new_user += &overrides;
+                let overrides = if *stdin_overrides {
+                    read_stdin_overrides()?
+                } else {
+                    read_overrides(cli, &profile)?
+                };
+                let header = overrides_header(cli, this_version.as_deref());
+                new_user = userjs::append_overrides(&new_user, header.as_deref(), &overrides);
             }
 
-            fs::write(profile.join("user.js"), &new_user)?;
+            if *strip_comments {
+                new_user = userjs::strip_comments(&new_user);
+            }
+
+            report.old_version.clone_from(&existing_version);
+            report.new_version.clone_from(&this_version);
+            report.changed = Some(existing_user != new_user);
+
+            if *fail_on_change {
+                if existing_user == new_user {
+                    status!(
+                        "{} user.js is up to date with arkenfox v{}",
+                        "unchanged".dimmed(),
+                        display_version(this_version.as_deref()).green(),
+                    );
+                    return Ok(());
+                }
+
+                if *diff {
+                    print_diff(&existing_user, &new_user);
+                }
+
+                if let Some(diff_output) = diff_output {
+                    write_diff(diff_output, "user.js", &existing_user, &new_user)?;
+                }
+
+                bail!(
+                    "user.js is out of date (arkenfox v{} -> v{}); run `arkencrab update` to update it",
+                    display_version(existing_version.as_deref()),
+                    display_version(this_version.as_deref()),
+                );
+            }
+
+            if existing_user == new_user && !cli.keep_backup_on_unchanged {
+                status!(
+                    "{} arkenfox v{}, nothing to do",
+                    "unchanged".dimmed(),
+                    display_version(this_version.as_deref())
+                );
+                return Ok(());
+            }
+
+            let backup = backup_file(cli, &profile, "user.js", &existing_user)?;
+            report.backup = Some(profile.join(&backup));
+
+            write_profile_file(cli, &profile, "user.js", &new_user, &backup)?;
 
             if *diff {
                 print_diff(&existing_user, &new_user);
             }
 
-            println!(
+            if let Some(diff_output) = diff_output {
+                write_diff(diff_output, "user.js", &existing_user, &new_user)?;
+            }
+
+            status!(
                 "{} arkenfox v{} {} v{}{}",
                 "updated".green(),
-                if existing_version == this_version {
-                    existing_version.clone()
-                } else {
-                    existing_version.yellow().to_string()
+                match ordering {
+                    Some(cmp::Ordering::Equal) => {
+                        display_version(existing_version.as_deref()).to_owned()
+                    }
+                    Some(cmp::Ordering::Greater) => display_version(existing_version.as_deref())
+                        .red()
+                        .to_string(),
+                    Some(cmp::Ordering::Less) | None => {
+                        display_version(existing_version.as_deref())
+                            .yellow()
+                            .to_string()
+                    }
                 },
                 "->".dimmed(),
-                this_version.green(),
-                if existing_version == this_version {
+                display_version(this_version.as_deref()).green(),
+                if ordering == Some(cmp::Ordering::Equal) {
                     if existing_user == new_user {
                         " (unchanged)".dimmed().to_string()
                     } else {
@@ -176,30 +1245,104 @@ fn main() -> Result<()> {
                     String::new()
                 }
             );
+
+            if cli.verbose && !dropped_prefs.is_empty() {
+                let existing_prefs = read_string_with_default(profile.join("prefs.js"))?;
+                let materialized = prefs::keys(&existing_prefs).collect::<HashSet<_>>();
+
+                status!(
+                    "{} arkenfox v{} no longer sets {} prefs:",
+                    "found".yellow(),
+                    display_version(this_version.as_deref()),
+                    dropped_prefs.len()
+                );
+
+                for key in &dropped_prefs {
+                    if materialized.contains(key) {
+                        status!("  {} {}", key, "(still set in prefs.js)".yellow());
+                    } else {
+                        status!("  {}", key.dimmed());
+                    }
+                }
+
+                if dropped_prefs.iter().any(|k| materialized.contains(k)) {
+                    status!(
+                        "values left in prefs.js by earlier versions stay in effect; reset them in {} unless you set them intentionally",
+                        "about:config".cyan()
+                    );
+                }
+            }
+
+            if let Some(hook) = hook
+                && existing_user != new_user
+            {
+                run_hook(hook, report)?;
+            }
         }
 
-        Command::PrefsClean { diff } => {
-            let profile = resolve_profile(&cli)?;
-            println!("{} {}", "using profile".blue(), profile.display());
+        Command::PrefsClean {
+            diff,
+            diff_output,
+            stats_only,
+            section,
+            require_marker,
+        } => {
+            let profile = resolve_profile(cli)?;
+            status!(
+                "{} {}",
+                "using profile".blue(),
+                display_path(cli, &profile, &profile).display()
+            );
+            warn_if_sandboxed(&profile);
+            report.profile = Some(profile.clone());
+
+            if !stats_only {
+                check_symlink(cli, &profile.join("prefs.js"))?;
+            }
 
             let user = read_string_with_default(profile.join("user.js"))?;
+
+            if *require_marker {
+                match userjs::reapply_base(&user) {
+                    userjs::Reapply::Base(base) if prefs::keys(&base).next().is_some() => {}
+                    userjs::Reapply::Base(_) => {
+                        bail!("user.js in {} does not set any prefs", profile.display());
+                    }
+                    userjs::Reapply::MissingBase | userjs::Reapply::Unmanaged => {
+                        bail!(
+                            "user.js in {} is not managed by arkencrab; run `arkencrab update` first or check that this is the right profile",
+                            profile.display()
+                        );
+                    }
+                }
+            }
             let existing_prefs = read_string_with_default(profile.join("prefs.js"))?;
 
-            let backup = Path::new("prefsjs_backups").join(format!("prefs.js.backup.{}", now()));
+            let (discarded_prefs, new_prefs) = if let Some(section) = section {
+                prefs::partition_redundant_by(&section_keys(&user, *section)?, &existing_prefs)
+            } else {
+                prefs::partition_redundant(&user, &existing_prefs)
+            };
+            report.prefs_redundant = Some(discarded_prefs.len());
 
-            fs::create_dir_all(profile.join("prefsjs_backups"))?;
-            fs::write(profile.join(&backup), &existing_prefs)?;
+            if *stats_only {
+                status!(
+                    "{} {} redundant prefs",
+                    "found".yellow(),
+                    discarded_prefs.len()
+                );
 
-            println!("{} prefs.js to {}", "backed up".magenta(), backup.display());
+                if cli.verbose {
+                    for key in discarded_prefs.iter().filter_map(|l| prefs::key(l)) {
+                        status!("  {}", key.dimmed());
+                    }
+                }
 
-            let user_pref_keys = REGEX_USER_PREF
-                .captures_iter(&user)
-                .map(|c| c.extract::<1>().1[0])
-                .collect::<HashSet<_>>();
+                return Ok(());
+            }
 
-            let (discarded_prefs, new_prefs): (Vec<_>, Vec<_>) = existing_prefs
-                .lines()
-                .partition(|l| user_pref_keys.iter().any(|k| l.contains(k)));
+            let backup = backup_file(cli, &profile, "prefs.js", &existing_prefs)?;
+            report.backup = Some(profile.join(&backup));
 
             let discarded_prefs = discarded_prefs.len();
             let new_prefs = new_prefs.join("\n") + "\n";
@@ -208,83 +1351,287 @@ fn main() -> Result<()> {
                 print_diff(&existing_prefs, &new_prefs);
             }
 
-            fs::write(profile.join("prefs.js"), &new_prefs)?;
-            println!("{} {} redundant prefs", "removed".red(), discarded_prefs);
+            if let Some(diff_output) = diff_output {
+                write_diff(diff_output, "prefs.js", &existing_prefs, &new_prefs)?;
+            }
+
+            write_profile_file(cli, &profile, "prefs.js", &new_prefs, &backup)?;
+            status!("{} {} redundant prefs", "removed".red(), discarded_prefs);
+            report.prefs_removed = Some(discarded_prefs);
         }
 
-        Command::Edit { editor, no_apply } => {
-            let profile = resolve_profile(&cli)?;
-            println!("{} {}", "using profile".blue(), profile.display());
+        Command::Edit {
+            editor,
+            no_apply,
+            watch,
+            overrides_only,
+            wait,
+            wait_timeout,
+            print_after,
+        } => {
+            let profile = resolve_profile(cli)?;
+            status!(
+                "{} {}",
+                "using profile".blue(),
+                display_path(cli, &profile, &profile).display()
+            );
+            warn_if_sandboxed(&profile);
+            report.profile = Some(profile.clone());
 
-            let mut editor = editor
-                .as_ref()
-                .and_then(|s| shlex::split(s))
-                .unwrap_or_else(|| vec![DEFAULT_EDITOR.to_owned()]);
+            let overrides = profile.join("user-overrides.js");
+            check_symlink(cli, &overrides)?;
+            if !no_apply {
+                check_symlink(cli, &profile.join("user.js"))?;
+            }
 
-            let program = editor
-                .pop()
+            let configured_editor = editor.clone().or_else(|| {
+                ["VISUAL", "EDITOR"]
+                    .into_iter()
+                    .find_map(|var| env::var(var).ok().filter(|e| !e.is_empty()))
+            });
+
+            let mut editor = configured_editor
+                .as_ref()
+                .map_or_else(
+                    || Some(vec![DEFAULT_EDITOR.to_owned()]),
+                    |s| shlex::split(s),
+                )
+                .filter(|e| !e.is_empty())
                 .ok_or_else(|| eyre!("invalid editor provided"))?;
 
+            let program = editor.remove(0);
+
+            if find_executable(&program).is_none() {
+                if configured_editor.is_some() {
+                    bail!(
+                        "editor `{program}` could not be found; configure a different one with `--editor`, $VISUAL, or $EDITOR"
+                    );
+                }
+
+                bail!(
+                    "no editor is configured and the default editor `{program}` could not be found; configure one with `--editor`, $VISUAL, or $EDITOR"
+                );
+            }
+
+            // The editor writes user-overrides.js itself, so the symlink is replaced just before
+            // it is opened rather than before writing.
+            replace_symlink(cli, &overrides)?;
+
+            let modified_before = fs::metadata(&overrides).and_then(|m| m.modified()).ok();
+
             let status = StdCommand::new(&program)
                 .args(&editor)
-                .arg(profile.join("user-overrides.js"))
+                .arg(&overrides)
                 .status()?;
 
             if !status.success() {
                 bail!("editor failed with status code {:?}", status.code())
             }
 
-            let existing_user = read_string_with_default(profile.join("user.js"))?;
-            let existing_version = find_version(&existing_user);
-
-            let mut new_user = existing_user
-                .lines()
-                .take_while(|l| l.trim() != ARKENCRAB_START_MARKER)
-                .collect::<Vec<_>>()
-                .join("\n");
+            if *wait || is_detaching_editor(&program, &editor) {
+                wait_for_edit(
+                    &overrides,
+                    modified_before,
+                    Duration::from_secs(*wait_timeout),
+                )?;
+            }
 
             if !no_apply {
-                if new_user.trim() == existing_user.trim() {
-                    println!(
-                        "{} automatically update user.js with new overrides; run {}",
-                        "could not".yellow(),
-                        "`arkencrab update`".cyan()
-                    );
-                } else {
-                    let backup =
-                        Path::new("userjs_backups").join(format!("user.js.backup.{}", now()));
+                apply_overrides(cli, &profile, report, *overrides_only)?;
+            }
 
-                    fs::create_dir_all(profile.join("userjs_backups"))?;
-                    fs::write(profile.join(&backup), &existing_user)?;
+            if *print_after {
+                print!("{}", read_string_with_default(&overrides)?);
+            }
 
-                    println!("{} user.js to {}", "backed up".magenta(), backup.display());
+            if *watch {
+                watch_overrides(cli, &profile, report, *overrides_only)?;
+            }
+        }
 
-                    let overrides = read_string_with_default(profile.join("user-overrides.js"))?;
-                    new_user += "\n";
-                    new_user += ARKENCRAB_START_MARKER;
-                    new_user += "\n\n";
-                    new_user += &overrides;
+        Command::Lint => {
+            let profile = resolve_profile(cli)?;
+            status!(
+                "{} {}",
+                "using profile".blue(),
+                display_path(cli, &profile, &profile).display()
+            );
+            report.profile = Some(profile.clone());
 
-                    fs::write(profile.join("user.js"), &new_user)?;
+            let (overrides, invalid_values) = read_checked_overrides(cli, &profile)?;
+            let conflicts = lint::conflicts(&prefs::values(&overrides));
 
-                    println!(
-                        "{} arkenfox v{} with new overrides",
-                        "updated".green(),
-                        existing_version.green(),
-                    );
+            for conflict in &conflicts {
+                eprintln!(
+                    "{} {} conflicts with {}: {}",
+                    "warning:".yellow(),
+                    conflict.first.cyan(),
+                    conflict.second.cyan(),
+                    conflict.reason
+                );
+            }
+
+            let problems = conflicts.len() + invalid_values;
+            if problems > 0 {
+                bail!("found {problems} problems in overrides");
+            }
+
+            status!("{} no problems in overrides", "found".green());
+        }
+
+        Command::Export {
+            include_defaults,
+            section,
+        } => {
+            let profile = resolve_profile(cli)?;
+            report.profile = Some(profile.clone());
+
+            let user = read_string_with_default(profile.join("user.js"))?;
+            let mut values = prefs::values(&user);
+
+            let section_keys = match section {
+                Some(section) => Some(
+                    section_keys(&user, *section)?
+                        .into_iter()
+                        .map(prefs::unquote)
+                        .collect::<HashSet<_>>(),
+                ),
+                None => None,
+            };
+
+            if let Some(section_keys) = &section_keys {
+                values.retain(|k, _| section_keys.contains(k));
+            }
+
+            let mut export = serde_json::Map::new();
+
+            if *include_defaults {
+                let existing_prefs = read_string_with_default(profile.join("prefs.js"))?;
+                let materialized = prefs::values(&existing_prefs);
+                let mut unapplied = Vec::new();
+
+                // prefs.js also holds prefs that user.js does not declare, so only the
+                // declared ones take its value. Those that Firefox has not written to
+                // prefs.js are either still at their default value or have not been applied
+                // yet.
+                for (key, value) in &mut values {
+                    match materialized.get(key) {
+                        Some(materialized) => value.clone_from(materialized),
+                        None => unapplied.push(key.clone()),
+                    }
+                }
+
+                export.insert("unapplied".to_owned(), unapplied.into());
+            }
+
+            export.insert("prefs".to_owned(), serde_json::to_value(values)?);
+            println!("{}", serde_json::to_string_pretty(&export)?);
+        }
+
+        Command::Backup { which } => {
+            let profile = resolve_profile(cli)?;
+            status!(
+                "{} {}",
+                "using profile".blue(),
+                display_path(cli, &profile, &profile).display()
+            );
+            report.profile = Some(profile.clone());
+
+            for name in which.file_names() {
+                match fs::read_to_string(profile.join(name)) {
+                    Ok(contents) => {
+                        let backup = backup_file(cli, &profile, name, &contents)?;
+                        report.backup = Some(profile.join(&backup));
+                    }
+                    Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                        status!("{} {name} does not exist", "skipped".dimmed());
+                    }
+                    Err(err) => return Err(err.into()),
                 }
             }
         }
 
-        Command::Profile {} => {
-            let profile = resolve_profile(&cli)?;
-            println!("{}", profile.display());
+        Command::Profile { file } => {
+            let profile = resolve_profile(cli)?;
+            report.profile = Some(profile.clone());
+
+            if let Some(file) = file {
+                println!("{}", profile.join(file).display());
+            } else {
+                println!("{}", profile.display());
+            }
+        }
+
+        #[cfg(feature = "self-update")]
+        Command::SelfUpdate { dry_run } => {
+            self_update::self_update(&http_client(cli)?, *dry_run)?;
         }
 
         Command::Completions { shell } => {
             clap_complete::generate(*shell, &mut Cli::command(), "arkencrab", &mut io::stdout());
         }
+
+        Command::InstallCompletions { shell, force } => {
+            completions::install(*shell, *force)?;
+        }
     }
 
     Ok(())
 }
+
+fn main() -> Result<()> {
+    color_eyre::install()?;
+
+    let matches = Cli::command().get_matches();
+    let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+    cli.select_profile(&matches)
+        .unwrap_or_else(|err| err.exit());
+
+    if cli.print_profile {
+        println!("{}", resolve_profile(&cli)?.display());
+        return Ok(());
+    }
+
+    if cli.template_out {
+        print!("{OVERRIDES_TEMPLATE}");
+        return Ok(());
+    }
+
+    let Some(command) = &cli.command else {
+        Cli::command()
+            .error(
+                clap::error::ErrorKind::MissingSubcommand,
+                "a subcommand is required unless `--print-profile` or `--template-out` is used",
+            )
+            .exit();
+    };
+
+    if cli.porcelain && command.prints_to_stdout() {
+        Cli::command()
+            .error(
+                clap::error::ErrorKind::ArgumentConflict,
+                format!(
+                    "`--porcelain` cannot be used with `{}` when it prints its result to stdout",
+                    command.name()
+                ),
+            )
+            .exit();
+    }
+
+    PORCELAIN.store(cli.porcelain, Ordering::Relaxed);
+
+    let mut report = Report::new(command.name());
+    let result = run(&cli, command, &mut report);
+
+    if let Some(path) = &cli.report {
+        report.error = result.as_ref().err().map(|err| format!("{err:#}"));
+        report.write(path, cli.report_append)?;
+    }
+
+    if cli.porcelain {
+        report.error = result.as_ref().err().map(|err| format!("{err:#}"));
+        print!("{}", report.porcelain());
+    }
+
+    result
+}
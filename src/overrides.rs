@@ -0,0 +1,333 @@
+// SPDX-FileCopyrightText: 2025 Ryan Cao <hello@ryanccn.dev>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    collections::HashMap,
+    fmt::Write as _,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use eyre::{Result, bail};
+
+use crate::{ARKENCRAB_START_MARKER, cli::MergeStrategy, prefs, read_string_with_default};
+
+/// The overrides of a profile, along with every file they were assembled from, in the
+/// order they were applied.
+#[derive(Debug, Clone, Default)]
+pub struct Overrides {
+    pub content: String,
+    pub sources: Vec<PathBuf>,
+}
+
+/// List the `.js` fragments in an overrides directory, sorted lexicographically by file
+/// name. A missing directory has no fragments.
+fn fragments_in(dir: &Path) -> Result<Vec<PathBuf>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err.into()),
+    };
+
+    let mut fragments = Vec::new();
+
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_file() && path.extension().is_some_and(|e| e == "js") {
+            fragments.push(path);
+        }
+    }
+
+    fragments.sort_by(|a, b| a.file_name().cmp(&b.file_name()));
+
+    Ok(fragments)
+}
+
+/// Concatenate override sources, resolving prefs that are set by more than one source
+/// according to `strategy`. Prefs set repeatedly within a single source are left alone.
+/// Each source is terminated with a newline so that sources never run into each other.
+fn merge(sources: &[(PathBuf, String)], strategy: MergeStrategy) -> Result<String> {
+    let mut first_sources = HashMap::<&str, &Path>::new();
+    let mut conflicts = Vec::new();
+    let mut content = String::new();
+
+    for (path, source) in sources {
+        if !content.is_empty() && !content.ends_with('\n') {
+            content += "\n";
+        }
+
+        for line in source.split_inclusive('\n') {
+            // The marker separates the arkenfox base of user.js from the overrides, so an
+            // override repeating it would be mistaken for the start of the block.
+            if line.trim() == ARKENCRAB_START_MARKER {
+                bail!(
+                    "{} contains the arkencrab overrides marker {ARKENCRAB_START_MARKER}; remove it",
+                    path.display()
+                );
+            }
+
+            if let Some(key) = prefs::key(line) {
+                match first_sources.get(key) {
+                    Some(first) if *first != path => {
+                        conflicts.push((key, *first, path.as_path()));
+
+                        if strategy == MergeStrategy::FirstWins {
+                            continue;
+                        }
+                    }
+                    Some(_) => {}
+                    None => {
+                        first_sources.insert(key, path);
+                    }
+                }
+            }
+
+            content += line;
+        }
+    }
+
+    if strategy == MergeStrategy::Error && !conflicts.is_empty() {
+        let conflicts = conflicts
+            .iter()
+            .fold(String::new(), |mut output, (key, first, path)| {
+                let _ = write!(
+                    output,
+                    "\n  {key} is set in {} and {}",
+                    first.display(),
+                    path.display()
+                );
+                output
+            });
+
+        bail!("conflicting overrides:{conflicts}");
+    }
+
+    Ok(content)
+}
+
+/// Read overrides from a stream such as standard input, checking them the same way as the
+/// overrides read from files. An empty stream has no overrides.
+pub fn read_from(mut reader: impl io::Read) -> Result<String> {
+    let mut source = String::new();
+    reader.read_to_string(&mut source)?;
+
+    merge(
+        &[(PathBuf::from("<stdin>"), source)],
+        MergeStrategy::LastWins,
+    )
+}
+
+/// The file name of the shared overrides that only apply to the profile named `name`.
+pub fn profile_file_name(name: &str) -> String {
+    format!("user-overrides.{name}.js")
+}
+
+/// Read the overrides of a profile, in this order:
+///
+/// 1. `user-overrides.js` in the `shared` directory, if any
+/// 2. `user-overrides.js` in the profile
+/// 3. every `.js` file in the profile's `user-overrides.d`, in lexicographic order of their
+///    file names (by bytes, so `10-foo.js` sorts before `9-bar.js`; zero-pad numeric prefixes)
+/// 4. `user-overrides.<name>.js` in the `shared` directory for the profile named `name`
+///
+/// Prefs set by more than one of the first three are resolved according to `strategy`, but
+/// the profile-specific file is merged over all of them and always wins.
+pub fn read(
+    profile: &Path,
+    shared: Option<&Path>,
+    name: Option<&str>,
+    strategy: MergeStrategy,
+) -> Result<Overrides> {
+    let mut paths = Vec::new();
+
+    if let Some(shared) = shared {
+        let base = shared.join("user-overrides.js");
+        if base.is_file() {
+            paths.push(base);
+        }
+    }
+
+    paths.push(profile.join("user-overrides.js"));
+    paths.extend(fragments_in(&profile.join("user-overrides.d"))?);
+
+    let mut sources = Vec::new();
+    for path in &paths {
+        sources.push((path.clone(), read_string_with_default(path)?));
+    }
+
+    let mut content = merge(&sources, strategy)?;
+
+    if let (Some(shared), Some(name)) = (shared, name) {
+        let profile_specific = shared.join(profile_file_name(name));
+
+        if profile_specific.is_file() {
+            // Every pref in the profile-specific file comes after the others, so Firefox
+            // always takes its value; merging last-wins keeps conflicts from being dropped.
+            content = merge(
+                &[
+                    (profile.join("user-overrides.js"), content),
+                    (
+                        profile_specific.clone(),
+                        fs::read_to_string(&profile_specific)?,
+                    ),
+                ],
+                MergeStrategy::LastWins,
+            )?;
+            paths.push(profile_specific);
+        }
+    }
+
+    Ok(Overrides {
+        content,
+        sources: paths,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::PathBuf};
+
+    use eyre::Result;
+
+    use crate::cli::MergeStrategy;
+
+    #[test]
+    fn fragments_are_appended_in_order() -> Result<()> {
+        let profile = env::temp_dir().join(format!("arkencrab-overrides-{}", std::process::id()));
+        let dir = profile.join("user-overrides.d");
+        fs::create_dir_all(&dir)?;
+
+        fs::write(profile.join("user-overrides.js"), "user_pref(\"a\", 1);")?;
+        fs::write(dir.join("20-c.js"), "user_pref(\"c\", 3);\n")?;
+        fs::write(dir.join("10-b.js"), "user_pref(\"b\", 2);")?;
+        fs::write(dir.join("README.md"), "ignored")?;
+
+        let overrides = super::read(&profile, None, None, MergeStrategy::LastWins);
+        fs::remove_dir_all(&profile)?;
+        let overrides = overrides?;
+
+        assert_eq!(
+            overrides.content,
+            "user_pref(\"a\", 1);\nuser_pref(\"b\", 2);\nuser_pref(\"c\", 3);\n"
+        );
+        assert_eq!(
+            overrides.sources,
+            [
+                profile.join("user-overrides.js"),
+                dir.join("10-b.js"),
+                dir.join("20-c.js")
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn profile_specific_overrides_always_win() -> Result<()> {
+        let root = env::temp_dir().join(format!(
+            "arkencrab-profile-overrides-{}",
+            std::process::id()
+        ));
+        let (profile, shared) = (root.join("profile"), root.join("shared"));
+        fs::create_dir_all(profile.join("user-overrides.d"))?;
+        fs::create_dir_all(&shared)?;
+
+        fs::write(shared.join("user-overrides.js"), "user_pref(\"a\", 1);\n")?;
+        fs::write(profile.join("user-overrides.js"), "user_pref(\"b\", 2);\n")?;
+        fs::write(
+            profile.join("user-overrides.d").join("10-c.js"),
+            "user_pref(\"c\", 3);\n",
+        )?;
+        fs::write(
+            shared.join("user-overrides.work.js"),
+            "user_pref(\"a\", 4);\n",
+        )?;
+
+        let work = [
+            MergeStrategy::LastWins,
+            MergeStrategy::FirstWins,
+            MergeStrategy::Error,
+        ]
+        .map(|strategy| super::read(&profile, Some(&shared), Some("work"), strategy));
+        let other = super::read(&profile, Some(&shared), Some("other"), MergeStrategy::Error);
+        fs::remove_dir_all(&root)?;
+
+        for work in work {
+            let work = work?;
+            assert_eq!(
+                work.content,
+                "user_pref(\"a\", 1);\nuser_pref(\"b\", 2);\nuser_pref(\"c\", 3);\nuser_pref(\"a\", 4);\n"
+            );
+            assert_eq!(
+                work.sources,
+                [
+                    shared.join("user-overrides.js"),
+                    profile.join("user-overrides.js"),
+                    profile.join("user-overrides.d").join("10-c.js"),
+                    shared.join("user-overrides.work.js"),
+                ]
+            );
+        }
+
+        assert_eq!(
+            other?.content,
+            "user_pref(\"a\", 1);\nuser_pref(\"b\", 2);\nuser_pref(\"c\", 3);\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn merge_resolves_conflicts_across_sources() -> Result<()> {
+        let sources = [
+            (
+                PathBuf::from("user-overrides.js"),
+                "user_pref(\"a\", 1);\nuser_pref(\"a\", 2);\n".to_owned(),
+            ),
+            (
+                PathBuf::from("user-overrides.d/10-b.js"),
+                "user_pref(\"a\", 3);\nuser_pref(\"b\", 4);\n".to_owned(),
+            ),
+        ];
+
+        assert_eq!(
+            super::merge(&sources, MergeStrategy::LastWins)?,
+            "user_pref(\"a\", 1);\nuser_pref(\"a\", 2);\nuser_pref(\"a\", 3);\nuser_pref(\"b\", 4);\n"
+        );
+
+        assert_eq!(
+            super::merge(&sources, MergeStrategy::FirstWins)?,
+            "user_pref(\"a\", 1);\nuser_pref(\"a\", 2);\nuser_pref(\"b\", 4);\n"
+        );
+
+        let err = super::merge(&sources, MergeStrategy::Error)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("\"a\" is set in user-overrides.js and user-overrides.d/10-b.js"));
+        assert!(!err.contains("\"b\""));
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_from_accepts_empty_input() -> Result<()> {
+        assert_eq!(super::read_from(&b""[..])?, "");
+        assert_eq!(
+            super::read_from(&b"user_pref(\"a\", 1);\n"[..])?,
+            "user_pref(\"a\", 1);\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn merge_rejects_marker() {
+        let sources = [(
+            PathBuf::from("user-overrides.js"),
+            format!("user_pref(\"a\", 1);\n{}\n", crate::ARKENCRAB_START_MARKER),
+        )];
+
+        assert!(super::merge(&sources, MergeStrategy::LastWins).is_err());
+    }
+}
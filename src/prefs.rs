@@ -0,0 +1,303 @@
+// SPDX-FileCopyrightText: 2025 Ryan Cao <hello@ryanccn.dev>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashSet},
+    str::FromStr,
+    sync::LazyLock,
+};
+
+use regex::{Regex, RegexBuilder};
+use serde_json::Value;
+
+use crate::ARKENCRAB_START_MARKER;
+
+static REGEX_USER_PREF: LazyLock<Regex> = LazyLock::new(|| {
+    RegexBuilder::new(r#"^\s*user_pref\(\s*("(?:[^"\\]|\\.)*")\s*,"#)
+        .multi_line(true)
+        .build()
+        .unwrap()
+});
+
+static REGEX_SECTION: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\s*/\*+\s*\[SECTION\s+(\d+)\]").unwrap());
+
+static REGEX_USER_PREF_VALUE: LazyLock<Regex> = LazyLock::new(|| {
+    RegexBuilder::new(
+        r#"^\s*user_pref\(\s*("(?:[^"\\]|\\.)*")\s*,\s*("(?:[^"\\]|\\.)*"|'(?:[^'\\]|\\.)*'|[^)]*?)\s*\)\s*;"#,
+    )
+    .multi_line(true)
+    .build()
+    .unwrap()
+});
+
+/// Extract the (quoted) key of every `user_pref` call in a user.js or prefs.js.
+pub fn keys(js: &str) -> impl Iterator<Item = &str> {
+    REGEX_USER_PREF
+        .captures_iter(js)
+        .map(|c| c.extract::<1>().1[0])
+}
+
+/// Find the (quoted) keys set by an old user.js that a new user.js no longer sets, in order
+/// of first appearance. arkenfox's internal `_user.js.parrot` pref is ignored.
+pub fn removed_keys<'a>(old: &'a str, new: &str) -> Vec<&'a str> {
+    let new_keys = keys(new).collect::<HashSet<_>>();
+    let mut seen = HashSet::new();
+
+    keys(old)
+        .filter(|k| *k != "\"_user.js.parrot\"" && !new_keys.contains(k) && seen.insert(*k))
+        .collect()
+}
+
+/// Extract the (quoted) key of a single `user_pref` line.
+pub fn key(line: &str) -> Option<&str> {
+    REGEX_USER_PREF
+        .captures(line)
+        .map(|c| c.extract::<1>().1[0])
+}
+
+/// Extract the (quoted) key and the raw value token of a single `user_pref` line.
+pub fn key_value(line: &str) -> Option<(&str, &str)> {
+    REGEX_USER_PREF_VALUE.captures(line).map(|c| {
+        let [key, value] = c.extract().1;
+        (key, value)
+    })
+}
+
+/// Unquote a key as extracted by [`keys`] or [`key`], falling back to the raw key if it is
+/// not a valid JSON string.
+pub fn unquote(key: &str) -> String {
+    serde_json::from_str(key).unwrap_or_else(|_| key.to_owned())
+}
+
+/// An inclusive range of arkenfox user.js sections, e.g. `0800` or `0800-0900`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SectionRange {
+    pub start: u32,
+    pub end: u32,
+}
+
+impl FromStr for SectionRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |n: &str| {
+            n.trim()
+                .parse::<u32>()
+                .map_err(|_| format!("invalid section number `{n}`"))
+        };
+
+        let (start, end) = match s.split_once('-') {
+            Some((start, end)) => (parse(start)?, parse(end)?),
+            None => (parse(s)?, parse(s)?),
+        };
+
+        if start > end {
+            return Err(format!("section range `{s}` is reversed"));
+        }
+
+        Ok(Self { start, end })
+    }
+}
+
+impl SectionRange {
+    pub fn contains(self, section: u32) -> bool {
+        (self.start..=self.end).contains(&section)
+    }
+}
+
+/// Extract the (quoted) keys of the `user_pref` calls in an arkenfox user.js that belong to
+/// a section in `range`, as marked by `[SECTION NNNN]` headers. Overrides appended by
+/// arkencrab do not belong to any section. Returns `None` if user.js has no sections.
+pub fn keys_in_sections(user_js: &str, range: SectionRange) -> Option<HashSet<&str>> {
+    let mut section = None;
+    let mut found_sections = false;
+    let mut keys = HashSet::new();
+
+    for line in user_js.lines() {
+        if line.trim() == ARKENCRAB_START_MARKER {
+            break;
+        }
+
+        if let Some(captures) = REGEX_SECTION.captures(line) {
+            section = captures[1].parse::<u32>().ok();
+            found_sections = true;
+        } else if let Some(key) = key(line)
+            && section.is_some_and(|s| range.contains(s))
+        {
+            keys.insert(key);
+        }
+    }
+
+    found_sections.then_some(keys)
+}
+
+/// Rewrite a single-quoted JS string literal as the equivalent double-quoted one.
+fn double_quote(inner: &str) -> String {
+    let mut quoted = String::from('"');
+    let mut chars = inner.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('\'') => quoted.push('\''),
+                Some(escaped) => {
+                    quoted.push('\\');
+                    quoted.push(escaped);
+                }
+                None => quoted.push_str("\\\\"),
+            },
+            '"' => quoted.push_str("\\\""),
+            c => quoted.push(c),
+        }
+    }
+
+    quoted.push('"');
+    quoted
+}
+
+/// Parse a JS literal as used in `user_pref` calls into a JSON value, falling back to the
+/// raw token as a string if it is not valid JSON. Single-quoted strings are read the same
+/// way as double-quoted ones, as Firefox does.
+fn parse_value(token: &str) -> Value {
+    let json = match token.strip_prefix('\'').and_then(|t| t.strip_suffix('\'')) {
+        Some(inner) => Cow::Owned(double_quote(inner)),
+        None => Cow::Borrowed(token),
+    };
+
+    serde_json::from_str(&json).unwrap_or_else(|_| Value::String(token.to_owned()))
+}
+
+/// Extract every `user_pref` call in a user.js or prefs.js as a map of unquoted keys to
+/// values. Later calls override earlier ones, mirroring how Firefox applies them.
+pub fn values(js: &str) -> BTreeMap<String, Value> {
+    REGEX_USER_PREF_VALUE
+        .captures_iter(js)
+        .map(|c| {
+            let [key, value] = c.extract().1;
+            (unquote(key), parse_value(value))
+        })
+        .collect()
+}
+
+/// Split the lines of prefs.js into those made redundant by user.js and those to keep.
+pub fn partition_redundant<'a>(user: &str, prefs: &'a str) -> (Vec<&'a str>, Vec<&'a str>) {
+    partition_redundant_by(&keys(user).collect(), prefs)
+}
+
+/// Split the lines of prefs.js into those setting one of `user_pref_keys` and those to keep.
+pub fn partition_redundant_by<'a>(
+    user_pref_keys: &HashSet<&str>,
+    prefs: &'a str,
+) -> (Vec<&'a str>, Vec<&'a str>) {
+    prefs
+        .lines()
+        .partition(|l| key(l).is_some_and(|k| user_pref_keys.contains(k)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, fs, path::Path};
+
+    use eyre::{OptionExt as _, Result};
+    use serde_json::json;
+
+    #[test]
+    fn partition_redundant_matches_substring_scan() -> Result<()> {
+        let root_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let user = fs::read_to_string(root_dir.join("src/user.test.js"))?;
+        let prefs = fs::read_to_string(root_dir.join("src/prefs.test.js"))?;
+
+        let user_pref_keys = super::keys(&user).collect::<HashSet<_>>();
+        let (old_discarded, old_kept): (Vec<_>, Vec<_>) = prefs
+            .lines()
+            .partition(|l| user_pref_keys.iter().any(|k| l.contains(k)));
+
+        let (discarded, kept) = super::partition_redundant(&user, &prefs);
+
+        assert_eq!(discarded, old_discarded);
+        assert_eq!(kept, old_kept);
+        assert_eq!(discarded.len(), 5);
+
+        Ok(())
+    }
+
+    #[test]
+    fn removed_keys_lists_dropped_prefs() {
+        let old = r#"user_pref("_user.js.parrot", "0100");
+user_pref("a", 1);
+user_pref("b", 2);
+user_pref("a", 3);
+"#;
+        let new = r#"user_pref("b", 2);
+user_pref("c", 4);
+"#;
+
+        assert_eq!(super::removed_keys(old, new), ["\"a\""]);
+        assert!(super::removed_keys(new, new).is_empty());
+    }
+
+    #[test]
+    fn keys_in_sections_follows_section_headers() -> Result<()> {
+        let root_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let user = fs::read_to_string(root_dir.join("src/user.test.js"))?;
+
+        let range = "0600-0800"
+            .parse::<super::SectionRange>()
+            .map_err(eyre::Report::msg)?;
+        let keys = super::keys_in_sections(&user, range).ok_or_eyre("no sections found")?;
+
+        assert_eq!(
+            keys,
+            HashSet::from([
+                "\"_user.js.parrot\"",
+                "\"network.dns.disablePrefetch\"",
+                "\"browser.urlbar.speculativeConnect.enabled\"",
+                "\"browser.search.suggest.enabled\"",
+            ])
+        );
+
+        assert!(super::keys_in_sections("user_pref(\"a\", 1);", range).is_none());
+        assert!("0900-0800".parse::<super::SectionRange>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_value_reads_js_literals() {
+        assert_eq!(super::parse_value("true"), json!(true));
+        assert_eq!(super::parse_value("-1"), json!(-1));
+        assert_eq!(super::parse_value(r#""a \"b\"""#), json!("a \"b\""));
+        assert_eq!(super::parse_value("undefined"), json!("undefined"));
+    }
+
+    #[test]
+    fn parse_value_reads_single_quoted_strings() {
+        assert_eq!(super::parse_value("'x'"), json!("x"));
+        assert_eq!(
+            super::parse_value(r#"'it\'s "quoted"\n'"#),
+            json!("it's \"quoted\"\n")
+        );
+        assert_eq!(super::parse_value("'"), json!("'"));
+        assert_eq!(
+            serde_json::to_value(super::values("user_pref(\"s\", 'a)b');")).unwrap(),
+            json!({ "s": "a)b" })
+        );
+    }
+
+    #[test]
+    fn values_keeps_the_last_value_of_each_pref() {
+        let js = r#"user_pref("a", 1);
+user_pref("b", "x");
+  user_pref( "a" , false ) ;
+// user_pref("c", 3);
+"#;
+
+        assert_eq!(
+            serde_json::to_value(super::values(js)).unwrap(),
+            json!({ "a": false, "b": "x" })
+        );
+    }
+}
@@ -0,0 +1,32 @@
+// SPDX-FileCopyrightText: 2025 Ryan Cao <hello@ryanccn.dev>
+//
+// SPDX-License-Identifier: CC0-1.0
+
+// Mozilla User Preferences
+
+// DO NOT EDIT THIS FILE.
+//
+// If you make changes to this file while the application is running,
+// the changes will be overwritten when the application exits.
+//
+// To change a preference value, you can either:
+// - modify it via the UI (e.g. via about:config in the browser); or
+// - set it within a user.js file in your profile.
+
+user_pref("app.normandy.first_run", false);
+user_pref("app.update.lastUpdateTime.addon-background-update-timer", 1735689600);
+user_pref("browser.contentblocking.category", "strict");
+user_pref("browser.download.useDownloadDir", false);
+user_pref("browser.newtabpage.activity-stream.showSponsored", false);
+user_pref("browser.search.suggest.enabled", false);
+user_pref("browser.startup.homepage", "about:blank");
+user_pref("browser.startup.page", 0);
+user_pref("browser.uiCustomization.state", "{\"placements\":{\"nav-bar\":[\"back-button\"]}}");
+user_pref("extensions.pocket.enabled", false);
+user_pref("geo.provider.use_gpsd", false);
+user_pref("keyword.enabled", true);
+user_pref("network.dns.disablePrefetch", true);
+user_pref("network.trr.mode", 3);
+user_pref("privacy.resistFingerprinting", true);
+user_pref("privacy.sanitize.sanitizeOnShutdown", true);
+user_pref("toolkit.telemetry.reportingpolicy.firstRun", false);
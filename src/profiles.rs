@@ -5,47 +5,200 @@
 
 use std::{
     convert::AsRef,
-    env, io,
+    fmt::Write as _,
+    fs, io,
     path::{Path, PathBuf},
 };
 
-use eyre::{OptionExt, Result, bail};
+use anstream::eprintln;
+use eyre::{Result, bail, eyre};
 use ini::Ini;
+use owo_colors::OwoColorize as _;
 
-// `env::home_dir` stabilized in latest Rust but not in Nixpkgs Rust, so we implement
-// a knockoff version ourselves.
+use crate::dirs;
+
+/// Load a profiles.ini, pointing parse errors at the offending file and line.
+fn load_profiles_ini(profiles_ini: &Path) -> Result<Ini> {
+    match Ini::load_from_file(profiles_ini) {
+        Ok(ini) => Ok(ini),
+        Err(ini::Error::Parse(err)) => {
+            let mut message = format!(
+                "failed to parse {} at line {}, column {}: {}",
+                profiles_ini.display(),
+                err.line,
+                err.col,
+                err.msg
+            );
+
+            if let Some(line) = fs::read_to_string(profiles_ini)
+                .ok()
+                .and_then(|s| s.lines().nth(err.line.saturating_sub(1)).map(str::to_owned))
+            {
+                let _ = write!(message, "\n  {} | {}", err.line, line.trim_end());
+            }
+
+            Err(eyre!(message))
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Resolve the default profile recorded in a profiles.ini, skipping recorded defaults for
+/// which `exists` is false (e.g. a profile that has since been deleted). The defaults of
+/// `[Install...]` sections are tried first, followed by profiles marked `Default=1`.
+fn default_profile_path_in<T: AsRef<Path>>(
+    profiles_ini: T,
+    exists: impl Fn(&Path) -> bool,
+) -> Result<Option<PathBuf>> {
+    let profiles_ini = profiles_ini.as_ref();
+    let ini = load_profiles_ini(profiles_ini)?;
+    let dir = profiles_ini.parent().unwrap_or_else(|| Path::new(""));
+
+    let installs = ini.iter().filter_map(|(section, properties)| {
+        let section = section.filter(|s| s.starts_with("Install"))?;
+        Some((section, dir.join(properties.get("Default")?)))
+    });
+
+    let profiles = ini.iter().filter_map(|(section, properties)| {
+        let section = section.filter(|s| is_profile_section(s))?;
+        (properties.get("Default") == Some("1"))
+            .then(|| Some((section, section_profile_path(profiles_ini, properties)?)))?
+    });
+
+    let mut stale = Vec::new();
+
+    for (section, path) in installs.chain(profiles) {
+        if exists(&path) {
+            return Ok(Some(path));
+        }
+
+        if !stale.contains(&path) {
+            eprintln!(
+                "{} default profile {} in [{section}] of {} does not exist; skipping it",
+                "warning:".yellow(),
+                path.display(),
+                profiles_ini.display()
+            );
+            stale.push(path);
+        }
+    }
+
+    Ok(None)
+}
+
+fn is_profile_section(section: &str) -> bool {
+    section
+        .strip_prefix("Profile")
+        .is_some_and(|i| i.parse::<usize>().is_ok())
+}
+
+/// Resolve the path of the `[Profile<index>]` section in a profiles.ini, relative to the
+/// directory containing profiles.ini if the section has `IsRelative=1`.
+fn profile_path_by_index_in<T: AsRef<Path>>(profiles_ini: T, index: usize) -> Result<PathBuf> {
+    let profiles_ini = profiles_ini.as_ref();
+    let ini = load_profiles_ini(profiles_ini)?;
+    let section = format!("Profile{index}");
+
+    let Some(properties) = ini.section(Some(section.as_str())) else {
+        let mut indices = ini
+            .sections()
+            .flatten()
+            .filter_map(|s| s.strip_prefix("Profile")?.parse::<usize>().ok())
+            .collect::<Vec<_>>();
+        indices.sort_unstable();
+
+        if indices.is_empty() {
+            bail!(
+                "profile index {index} does not exist; {} has no profiles",
+                profiles_ini.display()
+            );
+        }
+
+        bail!(
+            "profile index {index} does not exist in {}; valid indices are {}",
+            profiles_ini.display(),
+            indices
+                .iter()
+                .map(|i| i.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    };
+
+    section_profile_path(profiles_ini, properties)
+        .ok_or_else(|| eyre!("[{section}] in {} has no Path", profiles_ini.display()))
+}
+
+/// Resolve the path of a profile section, relative to the directory containing
+/// profiles.ini if the section has `IsRelative=1`.
+fn section_profile_path(profiles_ini: &Path, properties: &ini::Properties) -> Option<PathBuf> {
+    let path = properties.get("Path")?;
+
+    if properties.get("IsRelative") == Some("1") {
+        Some(
+            profiles_ini
+                .parent()
+                .unwrap_or_else(|| Path::new(""))
+                .join(path),
+        )
+    } else {
+        Some(PathBuf::from(path))
+    }
+}
+
+/// List the paths of every `[Profile<index>]` section in a profiles.ini.
+fn profile_paths_in<T: AsRef<Path>>(profiles_ini: T) -> Result<Vec<PathBuf>> {
+    let profiles_ini = profiles_ini.as_ref();
+
+    Ok(load_profiles_ini(profiles_ini)?
+        .iter()
+        .filter(|(section, _)| section.is_some_and(is_profile_section))
+        .filter_map(|(_, properties)| section_profile_path(profiles_ini, properties))
+        .collect())
+}
+
+/// Whether a profile is locked by a running Firefox. Firefox holds a `lock` symlink
+/// pointing at `<address>:+<pid>` on Unix, and keeps `parent.lock` open without sharing
+/// on Windows.
 #[cfg(unix)]
-fn home_dir() -> Result<PathBuf> {
-    Ok(PathBuf::from(
-        env::var_os("HOME").ok_or_eyre("could not obtain home directory")?,
-    ))
+fn is_locked(profile: &Path) -> bool {
+    let Ok(target) = fs::read_link(profile.join("lock")) else {
+        return false;
+    };
+
+    // A crashed Firefox leaves a stale lock behind; check that the process still exists
+    // where the process table can be inspected.
+    let pid = target
+        .to_str()
+        .and_then(|t| t.rsplit_once(":+"))
+        .map(|(_, pid)| pid);
+
+    match pid {
+        Some(pid) if Path::new("/proc/self").exists() => Path::new("/proc").join(pid).exists(),
+        _ => true,
+    }
 }
 
 #[cfg(windows)]
-fn roaming_appdata() -> Result<PathBuf> {
-    let appdata = env::var_os("APPDATA").ok_or_eyre("could not obtain APPDATA directory")?;
-    Ok(PathBuf::from(appdata))
-}
-
-fn default_profile_path_in<T: AsRef<Path>>(profiles_ini: T) -> Result<String> {
-    Ini::load_from_file(profiles_ini)?
-        .into_iter()
-        .find_map(|(section_name, properties)| {
-            section_name
-                .is_some_and(|s| s.starts_with("Install"))
-                .then(|| properties.get("Default").map(|v| v.to_string()))
-        })
-        .flatten()
-        .ok_or_eyre("unable to obtain default profile from profiles.ini")
+fn is_locked(profile: &Path) -> bool {
+    use std::os::windows::fs::OpenOptionsExt as _;
+
+    let lock = profile.join("parent.lock");
+    lock.exists()
+        && fs::OpenOptions::new()
+            .read(true)
+            .share_mode(0)
+            .open(&lock)
+            .is_err()
 }
 
-pub fn default_profile() -> Result<PathBuf> {
+fn firefox_data_paths() -> Result<Vec<PathBuf>> {
     #[cfg(unix)]
-    let home = home_dir()?;
+    let home = dirs::home_dir()?;
     #[cfg(windows)]
-    let roaming_appdata = roaming_appdata()?;
+    let roaming_appdata = dirs::roaming_appdata()?;
 
-    let firefox_data_paths = [
+    Ok(vec![
         #[cfg(all(unix, not(target_os = "macos")))]
         home.join(".mozilla").join("firefox"),
         // Snap
@@ -68,40 +221,289 @@ pub fn default_profile() -> Result<PathBuf> {
             .join("Firefox"),
         #[cfg(windows)]
         roaming_appdata.join("Mozilla").join("Firefox"),
-    ];
+    ])
+}
 
-    for path in &firefox_data_paths {
+fn is_not_found(err: &eyre::Report) -> bool {
+    err.downcast_ref::<ini::Error>()
+        .is_some_and(|err| match err {
+            ini::Error::Io(err) => err.kind() == io::ErrorKind::NotFound,
+            ini::Error::Parse(_) => false,
+        })
+}
+
+/// Find the default profile in the first profiles.ini that has one, tracing each location
+/// that is checked to stderr if `verbose` is set.
+pub fn default_profile(verbose: bool) -> Result<PathBuf> {
+    for path in &firefox_data_paths()? {
         let profiles_ini = path.join("profiles.ini");
 
-        match default_profile_path_in(&profiles_ini) {
-            Ok(default_profile_path) => return Ok(path.join(default_profile_path)),
-            Err(err)
-                if err
-                    .downcast_ref::<ini::Error>()
-                    .is_some_and(|err| match err {
-                        ini::Error::Io(err) => err.kind() == io::ErrorKind::NotFound,
-                        ini::Error::Parse(_) => false,
-                    }) => {}
+        if verbose {
+            eprintln!("{} {}", "checking".dimmed(), profiles_ini.display());
+        }
+
+        match default_profile_path_in(&profiles_ini, Path::is_dir) {
+            Ok(Some(profile)) => {
+                if verbose {
+                    eprintln!("  found default profile {}", profile.display());
+                }
+                return Ok(profile);
+            }
+            Ok(None) => {
+                if verbose {
+                    eprintln!("  {}", "no existing default profile".dimmed());
+                }
+            }
+            Err(err) if is_not_found(&err) => {
+                if verbose {
+                    eprintln!("  {}", "does not exist".dimmed());
+                }
+            }
             Err(err) => return Err(err),
         }
     }
 
-    bail!("could not find default profile")
+    if verbose {
+        bail!("could not find default profile");
+    }
+
+    bail!("could not find default profile; pass --verbose to see which locations were checked")
+}
+
+/// Find the profile in the `[Profile<index>]` section of the first profiles.ini found.
+pub fn profile_by_index(index: usize) -> Result<PathBuf> {
+    for path in &firefox_data_paths()? {
+        match profile_path_by_index_in(path.join("profiles.ini"), index) {
+            Ok(profile) => return Ok(profile),
+            Err(err) if is_not_found(&err) => {}
+            Err(err) => return Err(err),
+        }
+    }
+
+    bail!("could not find profiles.ini")
+}
+
+/// The name of a profile as recorded in profiles.ini, falling back to the name of its
+/// directory for profiles that are not listed there.
+pub fn profile_name(profile: &Path) -> Option<String> {
+    let canonical = fs::canonicalize(profile).ok();
+
+    for path in firefox_data_paths().unwrap_or_default() {
+        let Ok(ini) = load_profiles_ini(&path.join("profiles.ini")) else {
+            continue;
+        };
+
+        for (section, properties) in &ini {
+            if !section.is_some_and(is_profile_section) {
+                continue;
+            }
+
+            let Some(candidate) = section_profile_path(&path.join("profiles.ini"), properties)
+            else {
+                continue;
+            };
+
+            if (candidate == profile
+                || canonical.is_some() && fs::canonicalize(&candidate).ok() == canonical)
+                && let Some(name) = properties.get("Name")
+            {
+                return Some(name.to_owned());
+            }
+        }
+    }
+
+    profile
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+}
+
+/// Find the single profile that is currently in use by a running Firefox.
+pub fn running_profile() -> Result<PathBuf> {
+    let mut locked = Vec::new();
+
+    for path in &firefox_data_paths()? {
+        match profile_paths_in(path.join("profiles.ini")) {
+            Ok(profiles) => {
+                for profile in profiles {
+                    if is_locked(&profile) && !locked.contains(&profile) {
+                        locked.push(profile);
+                    }
+                }
+            }
+            Err(err) if is_not_found(&err) => {}
+            Err(err) => return Err(err),
+        }
+    }
+
+    match locked.len() {
+        0 => bail!("no profile is in use by a running Firefox"),
+        1 => Ok(locked.remove(0)),
+        _ => bail!(
+            "multiple profiles are in use by running Firefox instances; pass --profile to pick one of:{}",
+            locked.iter().fold(String::new(), |mut output, p| {
+                let _ = write!(output, "\n  {}", p.display());
+                output
+            })
+        ),
+    }
+}
+
+/// A sandboxed packaging of Firefox that a profile belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sandbox {
+    Flatpak,
+    Snap,
+}
+
+impl Sandbox {
+    /// Advice for making sure that changes made from outside the sandbox take effect.
+    pub fn advice(self) -> &'static str {
+        match self {
+            Self::Flatpak => {
+                "the Flatpak Firefox may keep running in the background and overwrite changes; stop it fully with `flatpak kill org.mozilla.firefox` before making changes"
+            }
+            Self::Snap => {
+                "the Snap Firefox only picks up changes after a full restart; close every Firefox window (check with `snap ps firefox`) before making changes"
+            }
+        }
+    }
+}
+
+/// Detect whether a profile belongs to a sandboxed Firefox from its location.
+pub fn sandbox(profile: &Path) -> Option<Sandbox> {
+    let components = profile
+        .components()
+        .map(|c| c.as_os_str())
+        .collect::<Vec<_>>();
+
+    if components
+        .windows(3)
+        .any(|w| w[0] == ".var" && w[1] == "app" && w[2] == "org.mozilla.firefox")
+    {
+        Some(Sandbox::Flatpak)
+    } else if components
+        .windows(2)
+        .any(|w| w[0] == "snap" && w[1] == "firefox")
+    {
+        Some(Sandbox::Snap)
+    } else {
+        None
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use eyre::Result;
-    use std::path::Path;
+    use std::{env, path::Path};
 
     #[test]
     fn can_find_default_profile_path() -> Result<()> {
         let root_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
         let profiles_ini = root_dir.join("src/profiles.test.ini");
 
-        let result = super::default_profile_path_in(&profiles_ini)?;
-        assert_eq!(result, "Profiles/arkenfox");
+        let result = super::default_profile_path_in(&profiles_ini, |_| true)?;
+        assert_eq!(result, Some(root_dir.join("src/Profiles/arkenfox")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn skips_stale_default_profiles() -> Result<()> {
+        let root_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let profiles_ini = root_dir.join("src/profiles.stale.test.ini");
+
+        let result =
+            super::default_profile_path_in(&profiles_ini, |p| p.ends_with("Profiles/arkenfox"))?;
+        assert_eq!(result, Some(root_dir.join("src/Profiles/arkenfox")));
+
+        let result = super::default_profile_path_in(&profiles_ini, |_| false)?;
+        assert_eq!(result, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn can_find_profile_path_by_index() -> Result<()> {
+        let root_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let profiles_ini = root_dir.join("src/profiles.test.ini");
+
+        let result = super::profile_path_by_index_in(&profiles_ini, 0)?;
+        assert_eq!(result, root_dir.join("src/Profiles/arkenfox"));
+
+        let err = super::profile_path_by_index_in(&profiles_ini, 3)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("valid indices are 0"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn can_list_profile_paths() -> Result<()> {
+        let root_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let profiles_ini = root_dir.join("src/profiles.test.ini");
+
+        let result = super::profile_paths_in(&profiles_ini)?;
+        assert_eq!(result, [root_dir.join("src/Profiles/arkenfox")]);
 
         Ok(())
     }
+
+    #[cfg(unix)]
+    #[test]
+    fn detects_locked_profiles() -> Result<()> {
+        use std::{env, fs, os::unix::fs::symlink};
+
+        let profile = env::temp_dir().join(format!("arkencrab-lock-{}", std::process::id()));
+        fs::create_dir_all(&profile)?;
+
+        let unlocked = super::is_locked(&profile);
+        symlink(
+            format!("127.0.0.1:+{}", std::process::id()),
+            profile.join("lock"),
+        )?;
+        let locked = super::is_locked(&profile);
+
+        fs::remove_dir_all(&profile)?;
+
+        assert!(!unlocked);
+        assert!(locked);
+
+        Ok(())
+    }
+
+    #[test]
+    fn detects_sandboxed_profiles() {
+        use super::{Sandbox, sandbox};
+
+        assert_eq!(
+            sandbox(Path::new(
+                "/home/user/.var/app/org.mozilla.firefox/.mozilla/firefox/abc.default"
+            )),
+            Some(Sandbox::Flatpak)
+        );
+        assert_eq!(
+            sandbox(Path::new(
+                "/home/user/snap/firefox/common/.mozilla/firefox/abc.default"
+            )),
+            Some(Sandbox::Snap)
+        );
+        assert_eq!(
+            sandbox(Path::new("/home/user/.mozilla/firefox/abc.default")),
+            None
+        );
+    }
+
+    #[test]
+    fn parse_errors_mention_file_and_line() {
+        let root_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let profiles_ini = root_dir.join("src/profiles.malformed.test.ini");
+
+        let err = super::default_profile_path_in(&profiles_ini, |_| true)
+            .unwrap_err()
+            .to_string();
+
+        assert!(err.contains(&profiles_ini.display().to_string()));
+        assert!(err.contains("6 | =Profiles/arkenfox"));
+    }
 }
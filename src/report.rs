@@ -0,0 +1,123 @@
+// SPDX-FileCopyrightText: 2025 Ryan Cao <hello@ryanccn.dev>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    fmt::Write as _,
+    fs::{self, OpenOptions},
+    io::Write as _,
+    path::{Path, PathBuf},
+};
+
+use eyre::Result;
+use serde::Serialize;
+
+/// Bumped whenever a field is removed or changes meaning; adding fields is not a breaking change.
+const SCHEMA_VERSION: u32 = 1;
+
+/// A machine-readable summary of a single run.
+#[derive(Serialize, Debug, Clone)]
+pub struct Report {
+    pub schema_version: u32,
+    pub command: &'static str,
+    pub profile: Option<PathBuf>,
+    pub old_version: Option<String>,
+    pub new_version: Option<String>,
+    pub changed: Option<bool>,
+    pub backup: Option<PathBuf>,
+    pub prefs_removed: Option<usize>,
+    pub prefs_redundant: Option<usize>,
+    pub error: Option<String>,
+}
+
+impl Report {
+    pub fn new(command: &'static str) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            command,
+            profile: None,
+            old_version: None,
+            new_version: None,
+            changed: None,
+            backup: None,
+            prefs_removed: None,
+            prefs_redundant: None,
+            error: None,
+        }
+    }
+
+    /// Format the report as `key=value` lines. Every key is always present (with an empty
+    /// value if unset); existing keys and their order will not change across releases, and
+    /// new keys are only ever appended.
+    pub fn porcelain(&self) -> String {
+        fn display<T: ToString>(value: Option<T>) -> String {
+            value.map(|v| v.to_string()).unwrap_or_default()
+        }
+
+        let fields = [
+            ("schema_version", self.schema_version.to_string()),
+            ("command", self.command.to_owned()),
+            (
+                "profile",
+                display(self.profile.as_ref().map(|p| p.display())),
+            ),
+            ("old_version", display(self.old_version.as_ref())),
+            ("new_version", display(self.new_version.as_ref())),
+            ("changed", display(self.changed.as_ref())),
+            ("backup", display(self.backup.as_ref().map(|p| p.display()))),
+            ("prefs_removed", display(self.prefs_removed.as_ref())),
+            ("error", display(self.error.as_ref())),
+            ("prefs_redundant", display(self.prefs_redundant.as_ref())),
+        ];
+
+        fields
+            .into_iter()
+            .fold(String::new(), |mut output, (key, value)| {
+                let _ = writeln!(output, "{key}={}", value.replace('\n', "\\n"));
+                output
+            })
+    }
+
+    /// Write the report as pretty-printed JSON, or append it as a single line of JSON
+    /// (one report per line) when `append` is set.
+    pub fn write(&self, path: &Path, append: bool) -> Result<()> {
+        if append {
+            let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+            writeln!(file, "{}", serde_json::to_string(self)?)?;
+        } else {
+            fs::write(path, serde_json::to_string_pretty(self)? + "\n")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    #[test]
+    fn porcelain_lists_every_key() {
+        let mut report = super::Report::new("update");
+        report.profile = Some(PathBuf::from("/tmp/profile"));
+        report.old_version = Some("128".to_owned());
+        report.new_version = Some("129".to_owned());
+        report.changed = Some(true);
+        report.error = Some("first\nsecond".to_owned());
+
+        assert_eq!(
+            report.porcelain(),
+            "schema_version=1
+command=update
+profile=/tmp/profile
+old_version=128
+new_version=129
+changed=true
+backup=
+prefs_removed=
+error=first\\nsecond
+prefs_redundant=
+"
+        );
+    }
+}
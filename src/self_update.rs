@@ -0,0 +1,205 @@
+// SPDX-FileCopyrightText: 2025 Ryan Cao <hello@ryanccn.dev>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    env,
+    fmt::Write as _,
+    fs,
+    io::{Cursor, Read as _},
+    path::Path,
+};
+
+use eyre::{OptionExt as _, Result, bail, eyre};
+use owo_colors::OwoColorize as _;
+use serde::Deserialize;
+use sha2::{Digest as _, Sha256};
+
+static RELEASES_URL: &str = "https://api.github.com/repos/ryanccn/arkencrab/releases/latest";
+
+#[derive(Deserialize, Debug)]
+struct Release {
+    tag_name: String,
+    assets: Vec<Asset>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+    digest: Option<String>,
+}
+
+/// The target triple that release binaries for the current platform are built for.
+fn target() -> Option<&'static str> {
+    match (env::consts::ARCH, env::consts::OS) {
+        ("x86_64", "linux") => Some("x86_64-unknown-linux-musl"),
+        ("aarch64", "linux") => Some("aarch64-unknown-linux-musl"),
+        ("x86_64", "macos") => Some("x86_64-apple-darwin"),
+        ("aarch64", "macos") => Some("aarch64-apple-darwin"),
+        ("x86_64", "windows") => Some("x86_64-pc-windows-msvc"),
+        ("aarch64", "windows") => Some("aarch64-pc-windows-msvc"),
+        _ => None,
+    }
+}
+
+/// Parse a `major.minor.patch` version, ignoring a leading `v` and any pre-release or
+/// build metadata.
+fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let version = version.trim_start_matches('v');
+    let version = version.split(['-', '+']).next()?;
+
+    let mut parts = version.split('.').map(|p| p.parse::<u64>().ok());
+    let version = (parts.next()??, parts.next()??, parts.next()??);
+
+    parts.next().is_none().then_some(version)
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .fold(String::with_capacity(64), |mut s, b| {
+            let _ = write!(s, "{b:02x}");
+            s
+        })
+}
+
+/// Replace the executable at `exe` with `binary`. The new binary is written next to the
+/// existing one and renamed over it so that the executable is never left half-written.
+fn replace_executable(exe: &Path, binary: &[u8]) -> Result<()> {
+    let staged = exe.with_extension("new");
+    fs::write(&staged, binary)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt as _;
+        fs::set_permissions(&staged, fs::Permissions::from_mode(0o755))?;
+    }
+
+    // Windows does not allow replacing a running executable, but does allow renaming it.
+    #[cfg(windows)]
+    let old = exe.with_extension("old");
+    #[cfg(windows)]
+    {
+        let _ = fs::remove_file(&old);
+        if let Err(err) = fs::rename(exe, &old) {
+            let _ = fs::remove_file(&staged);
+            return Err(err.into());
+        }
+    }
+
+    if let Err(err) = fs::rename(&staged, exe) {
+        let _ = fs::remove_file(&staged);
+
+        // Put the running executable back so that arkencrab is not left missing.
+        #[cfg(windows)]
+        let _ = fs::rename(&old, exe);
+
+        return Err(err.into());
+    }
+
+    Ok(())
+}
+
+pub fn self_update(http: &reqwest::blocking::Client, dry_run: bool) -> Result<()> {
+    let current = env!("CARGO_PKG_VERSION");
+
+    let release = http
+        .get(RELEASES_URL)
+        .header("Accept", "application/vnd.github+json")
+        .send()?
+        .error_for_status()?
+        .json::<Release>()?;
+
+    let latest = release.tag_name.trim_start_matches('v');
+
+    let is_newer = parse_version(latest)
+        .zip(parse_version(current))
+        .is_some_and(|(latest, current)| latest > current);
+
+    if !is_newer {
+        status!(
+            "{} arkencrab v{} is the latest version",
+            "unchanged".dimmed(),
+            current
+        );
+        return Ok(());
+    }
+
+    status!(
+        "{} arkencrab v{} {} v{}",
+        "available".blue(),
+        current.yellow(),
+        "->".dimmed(),
+        latest.green()
+    );
+
+    if dry_run {
+        return Ok(());
+    }
+
+    let target = target().ok_or_eyre("no release binaries are available for this platform")?;
+    let asset_name = format!("arkencrab-{target}.zip");
+
+    let asset = release
+        .assets
+        .iter()
+        .find(|a| a.name == asset_name)
+        .ok_or_else(|| eyre!("release v{latest} does not include {asset_name}"))?;
+
+    let expected = asset
+        .digest
+        .as_deref()
+        .and_then(|d| d.strip_prefix("sha256:"))
+        .ok_or_else(|| eyre!("release v{latest} does not provide a checksum for {asset_name}"))?;
+
+    let archive = http
+        .get(&asset.browser_download_url)
+        .send()?
+        .error_for_status()?
+        .bytes()?;
+
+    let actual = sha256_hex(&archive);
+    if !actual.eq_ignore_ascii_case(expected) {
+        bail!("checksum mismatch for {asset_name}: expected {expected}, got {actual}");
+    }
+
+    let mut archive = zip::ZipArchive::new(Cursor::new(archive))?;
+    let mut binary = Vec::new();
+    archive
+        .by_name(&format!("arkencrab{}", env::consts::EXE_SUFFIX))?
+        .read_to_end(&mut binary)?;
+
+    let exe = env::current_exe()?;
+    replace_executable(&exe, &binary)?;
+
+    status!(
+        "{} arkencrab v{} {} v{}",
+        "updated".green(),
+        current.yellow(),
+        "->".dimmed(),
+        latest.green()
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn parse_version_handles_tags() {
+        assert_eq!(super::parse_version("v0.2.0"), Some((0, 2, 0)));
+        assert_eq!(super::parse_version("1.10.3-rc.1"), Some((1, 10, 3)));
+        assert_eq!(super::parse_version("1.2"), None);
+        assert_eq!(super::parse_version("nightly"), None);
+        assert!(super::parse_version("0.10.0") > super::parse_version("0.9.9"));
+    }
+
+    #[test]
+    fn sha256_hex_matches_known_digest() {
+        assert_eq!(
+            super::sha256_hex(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+}
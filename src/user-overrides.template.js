@@ -0,0 +1,15 @@
+/*** user-overrides.js ***/
+// Overrides for the arkenfox user.js, applied by arkencrab after the arkenfox prefs.
+// Prefs set here take precedence over those in arkenfox; see the arkenfox wiki for
+// suggestions: https://github.com/arkenfox/user.js/wiki/3.1-Overrides
+//
+// Apply changes with `arkencrab update`, or edit this file with `arkencrab edit`.
+
+/* restore the previous session on startup ***/
+// user_pref("browser.startup.page", 3);
+
+/* allow searching from the location bar ***/
+// user_pref("keyword.enabled", true);
+
+/* keep history when Firefox is closed ***/
+// user_pref("privacy.clearOnShutdown_v2.historyFormDataAndDownloads", false);
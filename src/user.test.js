@@ -0,0 +1,46 @@
+// SPDX-FileCopyrightText: 2025 Ryan Cao <hello@ryanccn.dev>
+//
+// SPDX-License-Identifier: CC0-1.0
+
+/******
+*    name: arkenfox user.js
+*    date: 1 January 2025
+* version: 128
+*    urls: https://github.com/arkenfox/user.js [repo]
+******/
+
+/* START: internal custom pref to test for syntax errors ***/
+user_pref("_user.js.parrot", "START: Oh yes, the Norwegian Blue... what's wrong with it?");
+
+/*** [SECTION 0100]: STARTUP ***/
+user_pref("_user.js.parrot", "0100 syntax error: the parrot's dead!");
+/* 0102: set startup page [SETUP-CHROME]
+ * 0=blank, 1=home, 2=about:home, 3=resume previous session
+ * [SETTING] General>Startup>Open previous windows and tabs ***/
+user_pref("browser.startup.page", 0);
+/* 0103: set HOME+NEWWINDOW page ***/
+user_pref("browser.startup.homepage", "about:blank");
+
+/*** [SECTION 0600]: BLOCK IMPLICIT OUTBOUND ***/
+user_pref("_user.js.parrot", "0600 syntax error: the parrot's no more!");
+/* 0602: disable DNS prefetching ***/
+user_pref("network.dns.disablePrefetch", true);
+
+/*** [SECTION 0800]: LOCATION BAR / SEARCH BAR / SUGGESTIONS / HISTORY / FORMS ***/
+user_pref("_user.js.parrot", "0800 syntax error: the parrot's ceased to be!");
+/* 0801: disable location bar making speculative connections ***/
+user_pref("browser.urlbar.speculativeConnect.enabled", false);
+/* 0804: disable live search suggestions ***/
+user_pref("browser.search.suggest.enabled", false);
+
+/*** [SECTION 4500]: OPTIONAL RFP (resistFingerprinting) ***/
+user_pref("_user.js.parrot", "4500 syntax error: the parrot's popped 'is clogs");
+/* 4501: enable RFP ***/
+   // user_pref("privacy.resistFingerprinting", true); // [FF41+]
+
+/* END: internal custom pref to test for syntax errors ***/
+user_pref("_user.js.parrot", "SUCCESS: No no he's not dead, he's, he's restin'!");
+
+/** START: arkencrab overrides */
+
+user_pref("keyword.enabled", true);
@@ -0,0 +1,397 @@
+// SPDX-FileCopyrightText: 2025 Ryan Cao <hello@ryanccn.dev>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{cmp::Ordering, collections::HashSet, sync::LazyLock};
+
+use regex::Regex;
+
+use crate::{ARKENCRAB_START_MARKER, REGEX_VERSION, prefs};
+
+/// Matches the opening of the block comments that arkenfox wraps prefs only still used by
+/// Firefox ESR in, e.g. `/* ESR128.x still uses all the following prefs`, allowing for
+/// minor variations in spacing and the number of asterisks.
+static REGEX_ESR_BLOCK: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"/\*+([ \t]*ESR)").unwrap());
+
+/// Enable the prefs arkenfox keeps for Firefox ESR by turning the opening of each ESR block
+/// comment into a line comment, as arkenfox instructs.
+pub fn enable_esr(user_js: &str) -> String {
+    REGEX_ESR_BLOCK.replace_all(user_js, "//$1").into_owned()
+}
+
+/// Compare two arkenfox versions (as captured by `find_version`) numerically, so that e.g.
+/// `99` sorts before `128`. Returns `None` if either version is unknown or not a number.
+pub fn compare_versions(a: Option<&str>, b: Option<&str>) -> Option<Ordering> {
+    let a = a?.parse::<u32>().ok()?;
+    let b = b?.parse::<u32>().ok()?;
+
+    Some(a.cmp(&b))
+}
+
+/// Split a user.js at the arkencrab overrides marker into the base preceding it and the
+/// overrides block following it. The overrides block is `None` if there is no marker.
+pub fn split_overrides(user_js: &str) -> (String, Option<String>) {
+    let lines = user_js.lines().collect::<Vec<_>>();
+
+    match lines
+        .iter()
+        .position(|l| l.trim() == ARKENCRAB_START_MARKER)
+    {
+        Some(idx) => (lines[..idx].join("\n"), Some(lines[idx + 1..].join("\n"))),
+        None => (lines.join("\n"), None),
+    }
+}
+
+/// Find prefs that were appended to a user.js by hand rather than by arkencrab: the lines
+/// following the last line that also appears in `upstream`, if they set any prefs. Returns
+/// the user.js preceding that block and the block itself.
+pub fn manual_overrides<'a>(user_js: &'a str, upstream: &str) -> Option<(&'a str, &'a str)> {
+    if split_overrides(user_js).1.is_some() {
+        return None;
+    }
+
+    let upstream_lines = upstream
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .collect::<HashSet<_>>();
+
+    let mut offset = 0;
+    let mut block_start = None;
+
+    for line in user_js.split_inclusive('\n') {
+        offset += line.len();
+        if upstream_lines.contains(line.trim()) {
+            block_start = Some(offset);
+        }
+    }
+
+    let (base, block) = user_js.split_at(block_start?);
+    prefs::keys(block).next().is_some().then_some((base, block))
+}
+
+/// What reapplying overrides to an existing user.js would start from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reapply {
+    /// There is no arkenfox user.js (e.g. in a fresh profile) to apply overrides to.
+    MissingBase,
+    /// user.js was not generated by arkencrab, so there is no overrides block to replace.
+    Unmanaged,
+    /// The arkenfox base of user.js preceding its overrides block.
+    Base(String),
+}
+
+/// Determine the base that overrides can be reapplied onto in an existing user.js.
+pub fn reapply_base(user_js: &str) -> Reapply {
+    match split_overrides(user_js) {
+        (base, _) if base.trim().is_empty() => Reapply::MissingBase,
+        (_, None) => Reapply::Unmanaged,
+        (base, Some(_)) => Reapply::Base(base),
+    }
+}
+
+/// Render a custom header for the overrides block as line comments, substituting the
+/// `{version}` (arkenfox), `{arkencrab}` (arkencrab version) and `{date}` tokens.
+pub fn render_header(template: &str, version: &str, date: &str) -> String {
+    template
+        .replace("{version}", version)
+        .replace("{arkencrab}", env!("CARGO_PKG_VERSION"))
+        .replace("{date}", date)
+        .lines()
+        .fold(String::new(), |mut output, line| {
+            output += format!("// {line}").trim_end();
+            output += "\n";
+            output
+        })
+}
+
+/// Append an overrides block to a user.js after the arkencrab overrides marker, replacing
+/// any overrides block that is already present so that the marker only appears once. A
+/// rendered header is placed directly after the marker.
+pub fn append_overrides(user_js: &str, header: Option<&str>, overrides: &str) -> String {
+    let mut output = match split_overrides(user_js) {
+        (base, Some(_)) => base.trim_end().to_owned() + "\n",
+        (_, None) => user_js.to_owned(),
+    };
+
+    output += "\n";
+    output += ARKENCRAB_START_MARKER;
+    output += "\n";
+    if let Some(header) = header {
+        output += header;
+    }
+    output += "\n";
+    output += overrides;
+
+    output
+}
+
+/// Remove comments and blank lines from a user.js, keeping every statement and the
+/// arkencrab overrides marker intact. String literals are respected, so comment-like
+/// sequences inside pref values are left alone.
+///
+/// The version line of the arkenfox header is kept in a minimal comment so that the
+/// version can still be detected on subsequent runs.
+pub fn strip_comments(user_js: &str) -> String {
+    let mut output = String::with_capacity(user_js.len());
+    let mut in_block_comment = false;
+
+    if let Some(version_line) = REGEX_VERSION.find(user_js) {
+        output += "/*\n";
+        output += version_line.as_str();
+        output += "\n*/\n";
+    }
+
+    for line in user_js.lines() {
+        if !in_block_comment && line.trim() == ARKENCRAB_START_MARKER {
+            output += line.trim();
+            output += "\n";
+            continue;
+        }
+
+        let mut stripped = String::with_capacity(line.len());
+        let mut string_quote = None;
+        let mut chars = line.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            if in_block_comment {
+                if c == '*' && chars.peek() == Some(&'/') {
+                    chars.next();
+                    in_block_comment = false;
+                }
+                continue;
+            }
+
+            if let Some(quote) = string_quote {
+                stripped.push(c);
+                if c == '\\' {
+                    if let Some(escaped) = chars.next() {
+                        stripped.push(escaped);
+                    }
+                } else if c == quote {
+                    string_quote = None;
+                }
+                continue;
+            }
+
+            match (c, chars.peek()) {
+                ('/', Some('/')) => break,
+                ('/', Some('*')) => {
+                    chars.next();
+                    in_block_comment = true;
+                }
+                ('"' | '\'', _) => {
+                    string_quote = Some(c);
+                    stripped.push(c);
+                }
+                _ => stripped.push(c),
+            }
+        }
+
+        let stripped = stripped.trim_end();
+        if !stripped.trim().is_empty() {
+            output += stripped;
+            output += "\n";
+        }
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cmp::Ordering, fs, path::Path};
+
+    use eyre::Result;
+
+    #[test]
+    fn enable_esr_opens_esr_blocks() -> Result<()> {
+        let root_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let user_js = fs::read_to_string(root_dir.join("src/esr.test.js"))?;
+
+        let enabled = super::enable_esr(&user_js);
+
+        assert!(enabled.contains("\n// ESR128.x still uses"));
+        assert!(enabled.contains("\n//ESR115.x still uses"));
+        assert!(enabled.contains("\n// ESR140.x still uses"));
+        assert!(!enabled.contains("/*ESR") && !enabled.contains("/* ESR"));
+
+        // Identical to the previous literal replacement for the current format.
+        let current = "/* ESR128.x still uses all the following prefs\n// ***/\n";
+        assert_eq!(
+            super::enable_esr(current),
+            current.replace("/* ESR", "// ESR")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn compare_versions_numerically() {
+        use super::compare_versions;
+
+        assert_eq!(
+            compare_versions(Some("128"), Some("128")),
+            Some(Ordering::Equal)
+        );
+        assert_eq!(
+            compare_versions(Some("128"), Some("99")),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(
+            compare_versions(Some("99"), Some("128")),
+            Some(Ordering::Less)
+        );
+        assert_eq!(compare_versions(None, Some("128")), None);
+        assert_eq!(compare_versions(Some("128"), None), None);
+        assert_eq!(compare_versions(None, None), None);
+    }
+
+    #[test]
+    fn split_overrides_at_marker() {
+        let user_js =
+            "user_pref(\"a\", 1);\n/** START: arkencrab overrides */\n\nuser_pref(\"b\", 2);\n";
+
+        assert_eq!(
+            super::split_overrides(user_js),
+            (
+                "user_pref(\"a\", 1);".to_owned(),
+                Some("\nuser_pref(\"b\", 2);".to_owned())
+            )
+        );
+
+        assert_eq!(
+            super::split_overrides("user_pref(\"a\", 1);\n"),
+            ("user_pref(\"a\", 1);".to_owned(), None)
+        );
+    }
+
+    #[test]
+    fn reapply_base_requires_arkenfox_base() {
+        use super::{Reapply, reapply_base};
+
+        assert_eq!(reapply_base(""), Reapply::MissingBase);
+        assert_eq!(
+            reapply_base("/** START: arkencrab overrides */\n\nuser_pref(\"b\", 2);\n"),
+            Reapply::MissingBase
+        );
+        assert_eq!(reapply_base("user_pref(\"a\", 1);\n"), Reapply::Unmanaged);
+        assert_eq!(
+            reapply_base("user_pref(\"a\", 1);\n/** START: arkencrab overrides */\n"),
+            Reapply::Base("user_pref(\"a\", 1);".to_owned())
+        );
+    }
+
+    #[test]
+    fn append_overrides_replaces_existing_block() {
+        let upstream = "user_pref(\"a\", 1);\n";
+        let overrides = "user_pref(\"b\", 2);\n";
+
+        let once = super::append_overrides(upstream, None, overrides);
+        let twice = super::append_overrides(&once, None, overrides);
+
+        assert_eq!(once, twice);
+        assert_eq!(twice.matches(crate::ARKENCRAB_START_MARKER).count(), 1);
+        assert_eq!(
+            super::split_overrides(&twice),
+            (
+                "user_pref(\"a\", 1);\n".to_owned(),
+                Some("\nuser_pref(\"b\", 2);".to_owned())
+            )
+        );
+    }
+
+    #[test]
+    fn overrides_round_trip() -> Result<()> {
+        let root_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let upstream = fs::read_to_string(root_dir.join("src/esr.test.js"))?;
+        assert!(upstream.ends_with('\n'));
+
+        let overrides = "user_pref(\"a\", 1);\n\n// comment\nuser_pref(\"b\", \"/** */\");\n";
+        let header = super::render_header("v{version}", "128", "2025-01-01");
+
+        let applied = super::append_overrides(&upstream, Some(&header), overrides);
+
+        let super::Reapply::Base(base) = super::reapply_base(&applied) else {
+            panic!("overrides block was not recognized");
+        };
+        assert_eq!(base.trim_end(), upstream.trim_end());
+        assert_eq!(
+            super::append_overrides(&base, Some(&header), overrides),
+            applied
+        );
+        assert_eq!(
+            super::append_overrides(&applied, Some(&header), overrides),
+            applied
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn finds_manual_overrides() {
+        let upstream = "/* arkenfox */\nuser_pref(\"a\", 1);\n\nuser_pref(\"_user.js.parrot\", \"SUCCESS\");\n";
+        let manual = "\n// mine\nuser_pref(\"b\", 2);\n";
+        let user_js = format!("{upstream}{manual}");
+
+        assert_eq!(
+            super::manual_overrides(&user_js, upstream),
+            Some((upstream, manual))
+        );
+        assert_eq!(super::manual_overrides(upstream, upstream), None);
+        assert_eq!(
+            super::manual_overrides(&super::append_overrides(upstream, None, manual), upstream),
+            None
+        );
+    }
+
+    #[test]
+    fn header_follows_marker() {
+        let header =
+            super::render_header("arkenfox v{version}\n\nupdated {date}", "128", "2025-01-01");
+        assert_eq!(header, "// arkenfox v128\n//\n// updated 2025-01-01\n");
+
+        let user_js = super::append_overrides(
+            "user_pref(\"a\", 1);\n",
+            Some(&header),
+            "user_pref(\"b\", 2);\n",
+        );
+        assert!(user_js.contains(&format!(
+            "{}\n{header}\nuser_pref",
+            crate::ARKENCRAB_START_MARKER
+        )));
+
+        let (base, overrides) = super::split_overrides(&user_js);
+        assert_eq!(base, "user_pref(\"a\", 1);\n");
+        assert!(overrides.is_some_and(|o| o.ends_with("user_pref(\"b\", 2);")));
+    }
+
+    #[test]
+    fn strip_comments_keeps_prefs_and_marker() {
+        let user_js = r#"/******
+* version: 128
+******/
+/* 0102: set startup page ***/
+user_pref("browser.startup.page", 0); // trailing comment
+
+user_pref("browser.startup.homepage", "https://example.com/*not-a-comment*/"); /* 0103 */
+// user_pref("commented.out", true);
+
+/** START: arkencrab overrides */
+
+user_pref("keyword.enabled", true);
+"#;
+
+        assert_eq!(
+            super::strip_comments(user_js),
+            r#"/*
+* version: 128
+*/
+user_pref("browser.startup.page", 0);
+user_pref("browser.startup.homepage", "https://example.com/*not-a-comment*/");
+/** START: arkencrab overrides */
+user_pref("keyword.enabled", true);
+"#
+        );
+    }
+}
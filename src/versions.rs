@@ -0,0 +1,152 @@
+// SPDX-FileCopyrightText: 2025 Ryan Cao <hello@ryanccn.dev>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    cmp::Reverse,
+    fs,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+use anstream::eprintln;
+use eyre::Result;
+use owo_colors::OwoColorize as _;
+use serde::{Deserialize, Serialize};
+
+use crate::dirs;
+
+static TAGS_URL: &str = "https://api.github.com/repos/arkenfox/user.js/tags?per_page=100";
+
+/// How long fetched tags are reused before asking GitHub again.
+const CACHE_MAX_AGE: Duration = Duration::from_mins(10);
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Tag {
+    name: String,
+}
+
+/// An arkenfox release tag, and whether it is the release that master is currently at.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Version {
+    pub tag: String,
+    pub master: bool,
+}
+
+fn cache_path() -> Option<PathBuf> {
+    Some(dirs::cache_dir()?.join("arkencrab").join("tags.json"))
+}
+
+/// Read the cached tags, if they are no older than `max_age` (or of any age if `None`).
+fn read_cache(max_age: Option<Duration>) -> Option<Vec<Tag>> {
+    let path = cache_path()?;
+
+    if let Some(max_age) = max_age {
+        let modified = fs::metadata(&path).and_then(|m| m.modified()).ok()?;
+        if SystemTime::now().duration_since(modified).ok()? > max_age {
+            return None;
+        }
+    }
+
+    serde_json::from_str(&fs::read_to_string(path).ok()?).ok()
+}
+
+fn fetch_tags(http: &reqwest::blocking::Client) -> Result<Vec<Tag>> {
+    if let Some(tags) = read_cache(Some(CACHE_MAX_AGE)) {
+        return Ok(tags);
+    }
+
+    let tags = http
+        .get(TAGS_URL)
+        .header("Accept", "application/vnd.github+json")
+        .send()
+        .and_then(|r| r.error_for_status())
+        .and_then(|r| r.json::<Vec<Tag>>());
+
+    match tags {
+        Ok(tags) => {
+            if let Some(path) = cache_path() {
+                let cache = serde_json::to_string(&tags)?;
+                let _ = path
+                    .parent()
+                    .map_or(Ok(()), fs::create_dir_all)
+                    .and_then(|()| fs::write(&path, cache));
+            }
+            Ok(tags)
+        }
+        Err(err) => match read_cache(None) {
+            Some(tags) => {
+                eprintln!(
+                    "{} could not fetch arkenfox versions ({err}); showing cached versions",
+                    "warning:".yellow()
+                );
+                Ok(tags)
+            }
+            None => Err(err.into()),
+        },
+    }
+}
+
+/// Order tags from newest to oldest by their numeric components, e.g. `128.0` before
+/// `99.0`, marking the newest tag matching the major version of master.
+fn versions_from(mut tags: Vec<String>, master: Option<&str>) -> Vec<Version> {
+    let key = |tag: &str| {
+        tag.split('.')
+            .map(|p| p.parse::<u64>().unwrap_or(0))
+            .collect::<Vec<_>>()
+    };
+
+    tags.sort_by_key(|t| Reverse(key(t)));
+
+    let master_tag = master.and_then(|master| {
+        tags.iter()
+            .position(|t| t.split('.').next() == Some(master))
+    });
+
+    tags.into_iter()
+        .enumerate()
+        .map(|(i, tag)| Version {
+            tag,
+            master: Some(i) == master_tag,
+        })
+        .collect()
+}
+
+/// List the available arkenfox versions, newest first. `master` is the version of the
+/// user.js on master, if known.
+pub fn list(http: &reqwest::blocking::Client, master: Option<&str>) -> Result<Vec<Version>> {
+    let tags = fetch_tags(http)?;
+    Ok(versions_from(
+        tags.into_iter().map(|t| t.name).collect(),
+        master,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn versions_are_sorted_and_marked() {
+        let versions = super::versions_from(
+            vec![
+                "99.0".to_owned(),
+                "128.0".to_owned(),
+                "140.0".to_owned(),
+                "128.1".to_owned(),
+            ],
+            Some("128"),
+        );
+
+        assert_eq!(
+            versions
+                .iter()
+                .map(|v| (v.tag.as_str(), v.master))
+                .collect::<Vec<_>>(),
+            [
+                ("140.0", false),
+                ("128.1", true),
+                ("128.0", false),
+                ("99.0", false)
+            ]
+        );
+    }
+}